@@ -2,8 +2,10 @@ extern crate slack;
 extern crate termion;
 
 use failure::{Error, Fail};
+use serde_json::Value;
 use tui::layout::Rect;
 
+use components::command;
 use components::*;
 use data::loader;
 use data::loader::Loader;
@@ -20,12 +22,33 @@ pub struct App {
     // Components
     // TODO pub key_manager: KeyManager,
     pub channel_selector: ChannelSelector,
+    pub composer: Composer,
+    pub command_line: Composer,
+    pub completion: Option<Completion>,
+    /// Whether replies in the open thread are sent to the channel too.
+    pub broadcast_reply: bool,
+    /// The message being edited in the composer, if any.
+    pub editing: Option<(ChannelID, MessageID)>,
+    /// The message to delete once the user confirms it.
+    pub pending_deletion: Option<(ChannelID, MessageID)>,
+    /// Description of a partially typed chord or count, shown in the status bar.
+    pub pending_keys: String,
+    /// Key bindings shown in the help overlay, while it is open.
+    pub help: Option<Help>,
 }
 
 impl App {
     pub fn new(state: AppState, loader: Loader, size: Rect) -> App {
         App {
             channel_selector: ChannelSelector::new(),
+            composer: Composer::new(),
+            command_line: Composer::new(),
+            completion: None,
+            broadcast_reply: false,
+            editing: None,
+            pending_deletion: None,
+            pending_keys: String::new(),
+            help: None,
             //TODO key_manager: KeyManager::new(),
             loader,
             size,
@@ -60,16 +83,357 @@ impl App {
 
     pub fn select_channel_from_selector(&mut self) -> Result<(), Error> {
         if let Some(id) = self.channel_selector.select(&self.state.channels) {
-            self.async_load_channel_history(&id)?;
-            self.state.select_channel(id)
+            self.select_channel(id)
         } else {
             Ok(())
         }
     }
 
+    /// Switches to the channel and loads its history. An edit in progress is discarded, as it
+    /// belongs to a message in the previous channel.
+    fn select_channel(&mut self, id: ChannelID) -> Result<(), Error> {
+        self.cancel_editing();
+        self.async_load_channel_history(&id)?;
+        self.state.select_channel(id)
+    }
+
     pub fn async_load_channel_history(&mut self, channel_id: &ChannelID) -> Result<(), Error> {
+        let kind = self.channel_kind(channel_id);
         self.state.set_loading_state(true);
-        self.loader.load_channel_history(channel_id, None)
+        self.loader.load_channel_history(channel_id, kind, None)
+    }
+
+    fn channel_kind(&self, channel_id: &ChannelID) -> ChannelKind {
+        self.state
+            .channels
+            .get(channel_id)
+            .map(Channel::kind)
+            .unwrap_or(ChannelKind::Public)
+    }
+
+    /// Requests the page of history before the oldest known message in the selected channel, if
+    /// the user has scrolled close enough to the top of it.
+    ///
+    /// Scroll position is stored as the distance from the bottom, so older messages being prepended
+    /// to the canvas will not move the viewport.
+    pub fn load_older_history_if_needed(&mut self) -> Result<(), Error> {
+        if !self.state.should_load_older_history() {
+            return Ok(());
+        }
+
+        let channel_id = self.state.selected_channel_id().clone();
+        let oldest_id = self.state.messages.oldest_message_id(&channel_id).cloned();
+        let kind = self.channel_kind(&channel_id);
+
+        self.state.set_loading_state(true);
+        self.loader
+            .load_channel_history(&channel_id, kind, oldest_id.as_ref())
+    }
+
+    /// Tells Slack that the selected channel has been read, if the user can see its newest message.
+    pub fn mark_selected_channel_if_needed(&mut self) -> Result<(), Error> {
+        match self.state.message_to_mark() {
+            Some(message_id) => {
+                let channel_id = self.state.selected_channel_id().clone();
+                self.mark_channel(&channel_id, message_id)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the kind of the channel, unless it is a direct message. Those cannot be left or have
+    /// their topic and purpose changed.
+    fn managed_channel_kind(&self, channel_id: &ChannelID) -> Result<ChannelKind, Error> {
+        match self.channel_kind(channel_id) {
+            ChannelKind::DirectMessage | ChannelKind::GroupDirectMessage => Err(format_err!(
+                "Direct messages cannot be left or have a topic or purpose"
+            )),
+            kind => Ok(kind),
+        }
+    }
+
+    fn mark_channel(&mut self, channel_id: &ChannelID, message_id: MessageID) -> Result<(), Error> {
+        let kind = self.channel_kind(channel_id);
+        self.loader.mark_channel(channel_id, kind, &message_id)?;
+        self.state.set_last_read(channel_id, message_id);
+        Ok(())
+    }
+
+    /// Opens the thread of the selected message, or of the newest message if none is selected, and
+    /// loads its replies.
+    pub fn open_thread(&mut self) -> Result<(), Error> {
+        let channel_id = self.state.selected_channel_id().clone();
+        let newest_id = self
+            .state
+            .messages
+            .channel_iter(&channel_id)
+            .rev()
+            .filter_map(|message| match *message {
+                Message::Standard(ref message) if !message.is_thread_reply() => {
+                    Some(message.message_id.clone())
+                }
+                _ => None,
+            })
+            .next();
+        let thread_id = match self.state.selected_message {
+            Some(_) => self
+                .state
+                .selected_standard_message()
+                .map(|message| message.thread_id.clone()),
+            None => newest_id,
+        };
+
+        match thread_id {
+            Some(thread_id) => {
+                let kind = self.channel_kind(&channel_id);
+                self.loader
+                    .load_thread_replies(&channel_id, kind, &thread_id)?;
+                if self.state.selected_thread.as_ref() != Some(&thread_id) {
+                    self.cancel_editing();
+                }
+                self.state.open_thread(thread_id);
+                self.broadcast_reply = false;
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    pub fn close_thread(&mut self) {
+        self.cancel_editing();
+        self.broadcast_reply = false;
+        self.state.close_thread();
+    }
+
+    /// Completes the word before the cursor in the composer, or switches to the next candidate if
+    /// a completion is already in progress.
+    pub fn complete_word(&mut self) {
+        if let Some(ref mut completion) = self.completion {
+            completion.select_next();
+            self.composer
+                .replace_before_cursor(completion.word_start(), completion.selected());
+            return;
+        }
+
+        let completion = {
+            let (word_start, word) = self.composer.word_before_cursor();
+            Completion::start(word_start, word, &self.state.users, &self.state.channels)
+        };
+        if let Some(completion) = completion {
+            self.composer
+                .replace_before_cursor(completion.word_start(), completion.selected());
+            self.completion = Some(completion);
+        }
+    }
+
+    /// Completes the command name or argument before the cursor in the command line, or switches
+    /// to the next candidate if a completion is already in progress.
+    pub fn complete_command(&mut self) {
+        if let Some(ref mut completion) = self.completion {
+            completion.select_next();
+            self.command_line
+                .replace_before_cursor(completion.word_start(), completion.selected());
+            return;
+        }
+
+        let completion = {
+            let (word_start, _) = self.command_line.word_before_cursor();
+            let line = &self.command_line.text()[..self.command_line.cursor_pos()];
+            let candidates = command::completions(line, word_start, &self.state.channels);
+            Completion::from_candidates(word_start, candidates)
+        };
+        if let Some(completion) = completion {
+            self.command_line
+                .replace_before_cursor(completion.word_start(), completion.selected());
+            self.completion = Some(completion);
+        }
+    }
+
+    pub fn run_command(&mut self, command: Command) -> Result<input_manager::Outcome, Error> {
+        let channel_id = self.state.selected_channel_id().clone();
+
+        match command {
+            Command::Quit => return Ok(input_manager::Outcome::Quit),
+            Command::Join(name) => {
+                // Slack can only join public channels by name.
+                match self.state.channels.find_by_name(&name).map(Channel::kind) {
+                    Some(ChannelKind::Public) | None => {}
+                    Some(_) => return Err(format_err!("#{} is not a public channel", name)),
+                }
+                self.loader.join_channel(name)?
+            }
+            Command::Leave => {
+                let kind = self.managed_channel_kind(&channel_id)?;
+                self.loader.leave_channel(&channel_id, kind)?
+            }
+            Command::Topic(topic) => {
+                let kind = self.managed_channel_kind(&channel_id)?;
+                self.loader.set_topic(&channel_id, kind, topic)?
+            }
+            Command::Purpose(purpose) => {
+                let kind = self.managed_channel_kind(&channel_id)?;
+                self.loader.set_purpose(&channel_id, kind, purpose)?
+            }
+            Command::Open(name) => {
+                let id = match self.state.channels.find_by_name(&name) {
+                    Some(channel) => channel.id().clone(),
+                    None => return Err(format_err!("No channel named #{}", name)),
+                };
+                self.select_channel(id)?;
+            }
+            Command::Mark => {
+                let newest_id = self
+                    .state
+                    .messages
+                    .newest_channel_message(&channel_id)
+                    .map(|message| message.message_id.clone());
+                if let Some(message_id) = newest_id {
+                    self.mark_channel(&channel_id, message_id)?;
+                }
+            }
+            Command::React(name) => self.toggle_reaction(name)?,
+        }
+
+        Ok(input_manager::Outcome::Continue)
+    }
+
+    pub fn toggle_star_selected_channel(&mut self) -> Result<(), Error> {
+        let channel_id = self.state.selected_channel_id().clone();
+        match self.state.selected_channel().map(Channel::is_starred) {
+            Some(true) => self.loader.unstar_channel(&channel_id),
+            Some(false) => self.loader.star_channel(&channel_id),
+            None => Ok(()),
+        }
+    }
+
+    /// Adds a reaction to the selected message, or removes it if the user has already reacted
+    /// with it.
+    pub fn toggle_reaction(&mut self, name: String) -> Result<(), Error> {
+        let channel_id = self.state.selected_channel_id().clone();
+        let (message_id, has_reacted) = match self.state.selected_standard_message() {
+            Some(message) => (
+                message.message_id.clone(),
+                message.has_reaction_from(&name, &self.state.current_user_id),
+            ),
+            None => return Err(format_err!("No message selected")),
+        };
+
+        if has_reacted {
+            self.loader.remove_reaction(&channel_id, &message_id, name)
+        } else {
+            self.loader.add_reaction(&channel_id, &message_id, name)
+        }
+    }
+
+    /// Starts editing the selected message in the composer.
+    pub fn edit_selected_message(&mut self) -> Result<(), Error> {
+        let message_id = self.own_selected_message_id()?;
+        self.start_editing(message_id);
+        Ok(())
+    }
+
+    /// Starts editing the newest message the user sent in the open thread, or in the channel if no
+    /// thread is open.
+    pub fn edit_last_message(&mut self) {
+        let message_id = {
+            let channel_id = self.state.selected_channel_id();
+            let user_id = &self.state.current_user_id;
+            let message = match self.state.selected_thread {
+                Some(ref thread_id) => self
+                    .state
+                    .messages
+                    .thread_iter(channel_id, thread_id)
+                    .filter(|message| &message.user_id == user_id)
+                    .last(),
+                None => self
+                    .state
+                    .messages
+                    .channel_iter(channel_id)
+                    .rev()
+                    .filter_map(|message| match *message {
+                        Message::Standard(ref message) => Some(message),
+                        _ => None,
+                    })
+                    .find(|message| message.is_shown_in_channel() && &message.user_id == user_id),
+            };
+            message.map(|message| message.message_id.clone())
+        };
+
+        if let Some(message_id) = message_id {
+            self.start_editing(message_id);
+        }
+    }
+
+    fn start_editing(&mut self, message_id: MessageID) {
+        let channel_id = self.state.selected_channel_id().clone();
+        let text = match self.state.messages.get(&channel_id, &message_id) {
+            Some(&Message::Standard(ref message)) => {
+                encoding::decode(&message.body, &self.state.users, &self.state.channels)
+            }
+            _ => return,
+        };
+        self.composer.set_text(text);
+        self.editing = Some((channel_id, message_id));
+    }
+
+    /// Stops editing a message, discarding the changes.
+    pub fn cancel_editing(&mut self) {
+        if self.editing.take().is_some() {
+            self.composer.reset();
+        }
+    }
+
+    /// Asks the user to confirm deleting the selected message.
+    pub fn delete_selected_message(&mut self) -> Result<(), Error> {
+        let message_id = self.own_selected_message_id()?;
+        self.pending_deletion = Some((self.state.selected_channel_id().clone(), message_id));
+        Ok(())
+    }
+
+    /// Deletes the message waiting for confirmation if the user confirmed it.
+    pub fn confirm_deletion(&mut self, confirmed: bool) -> Result<(), Error> {
+        match self.pending_deletion.take() {
+            Some((channel_id, message_id)) if confirmed => {
+                self.loader.delete_message(&channel_id, &message_id)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn own_selected_message_id(&self) -> Result<MessageID, Error> {
+        match self.state.selected_standard_message() {
+            Some(message) if message.user_id == self.state.current_user_id => {
+                Ok(message.message_id.clone())
+            }
+            Some(_) => Err(format_err!("Only your own messages can be changed")),
+            None => Err(format_err!("No message selected")),
+        }
+    }
+
+    pub fn send_composed_message(&mut self) -> Result<(), Error> {
+        if self.composer.is_blank() {
+            // Deleting messages is done explicitly instead.
+            self.editing = None;
+            return Ok(());
+        }
+
+        let text = encoding::encode(
+            &self.composer.take_text(),
+            &self.state.users,
+            &self.state.channels,
+        );
+        if let Some((channel_id, message_id)) = self.editing.take() {
+            return self.loader.update_message(&channel_id, &message_id, text);
+        }
+
+        let channel_id = self.state.selected_channel_id().clone();
+        match self.state.selected_thread.clone() {
+            Some(thread_id) => {
+                self.loader
+                    .post_reply(&channel_id, &thread_id, text, self.broadcast_reply)
+            }
+            None => self.loader.post_message(&channel_id, text),
+        }
     }
 
     pub fn accept_task_result(&mut self, result: loader::TaskResult) -> Result<(), Error> {
@@ -79,40 +443,294 @@ impl App {
                 self.state.set_loading_state(false);
                 self.accept_channel_history(channel_id, response)
             }
+            TaskResult::ThreadReplies(channel_id, thread_id, response) => {
+                match response {
+                    Ok(messages) => self.accept_thread_replies(channel_id, thread_id, messages),
+                    Err(error) => self.state.add_channel_error_message(
+                        &channel_id,
+                        error.context("Could not load thread"),
+                    ),
+                }
+                Ok(())
+            }
+            TaskResult::PostMessage(channel_id, response) => {
+                // Successfully sent messages will appear through the RTM connection, so only
+                // errors need to be handled here.
+                if let Err(error) = response {
+                    self.state.add_channel_error_message(
+                        &channel_id,
+                        error.context("Could not send message"),
+                    );
+                }
+                Ok(())
+            }
+            TaskResult::UpdateMessage(channel_id, response) => {
+                // Edits and deletions also arrive through the RTM connection.
+                if let Err(error) = response {
+                    self.state.add_channel_error_message(
+                        &channel_id,
+                        error.context("Could not edit message"),
+                    );
+                }
+                Ok(())
+            }
+            TaskResult::DeleteMessage(channel_id, response) => {
+                if let Err(error) = response {
+                    self.state.add_channel_error_message(
+                        &channel_id,
+                        error.context("Could not delete message"),
+                    );
+                }
+                Ok(())
+            }
+            TaskResult::PostReply(channel_id, response) => {
+                // Show the reply right away; the copy from the RTM connection replaces it.
+                let side_channel = MessageSideChannel {
+                    channel_id: Some(channel_id.clone()),
+                    ..Default::default()
+                };
+                match response.and_then(|reply| MessageEvent::from_json(&reply, &side_channel)) {
+                    Ok(Some(event)) => self.state.apply_message_event(event),
+                    Ok(None) => {}
+                    Err(error) => self.state.add_channel_error_message(
+                        &channel_id,
+                        error.context("Could not send reply"),
+                    ),
+                }
+                Ok(())
+            }
+            TaskResult::JoinChannel(name, response) => self.accept_joined_channel(name, response),
+            TaskResult::LeaveChannel(channel_id, response) => {
+                match response {
+                    Ok(_) => {
+                        if let Some(channel) = self.state.channels.get_mut(&channel_id) {
+                            channel.set_is_member(false);
+                        }
+                    }
+                    Err(error) => self.state.add_channel_error_message(
+                        &channel_id,
+                        error.context("Could not leave channel"),
+                    ),
+                }
+                Ok(())
+            }
+            TaskResult::SetTopic(channel_id, response) => {
+                match response {
+                    Ok(response) => {
+                        if let Some(channel) = self.state.channels.get_mut(&channel_id) {
+                            channel.set_topic_text(response["topic"].as_str().map(String::from));
+                        }
+                    }
+                    Err(error) => self.state.add_channel_error_message(
+                        &channel_id,
+                        error.context("Could not set topic"),
+                    ),
+                }
+                Ok(())
+            }
+            TaskResult::SetPurpose(channel_id, response) => {
+                if let Err(error) = response {
+                    self.state.add_channel_error_message(
+                        &channel_id,
+                        error.context("Could not set purpose"),
+                    );
+                }
+                Ok(())
+            }
+            TaskResult::MarkChannel(channel_id, response) => {
+                if let Err(error) = response {
+                    self.state.add_channel_error_message(
+                        &channel_id,
+                        error.context("Could not mark channel as read"),
+                    );
+                }
+                Ok(())
+            }
+            TaskResult::AddReaction(channel_id, message_id, name, response) => {
+                // Slack also sends the reaction over the RTM connection, but applying it twice
+                // changes nothing.
+                match response {
+                    Ok(_) => self.state.apply_message_event(MessageEvent::ReactionAdded {
+                        channel_id,
+                        message_id,
+                        name,
+                        user_id: self.state.current_user_id.clone(),
+                    }),
+                    Err(error) => self.state.add_channel_error_message(
+                        &channel_id,
+                        error.context("Could not add reaction"),
+                    ),
+                }
+                Ok(())
+            }
+            TaskResult::RemoveReaction(channel_id, message_id, name, response) => {
+                match response {
+                    Ok(_) => self
+                        .state
+                        .apply_message_event(MessageEvent::ReactionRemoved {
+                            channel_id,
+                            message_id,
+                            name,
+                            user_id: self.state.current_user_id.clone(),
+                        }),
+                    Err(error) => self.state.add_channel_error_message(
+                        &channel_id,
+                        error.context("Could not remove reaction"),
+                    ),
+                }
+                Ok(())
+            }
+            TaskResult::Stars(response) => {
+                match response {
+                    Ok(response) => self.accept_stars(response),
+                    Err(error) => self
+                        .state
+                        .add_error_message(error.context("Could not load starred channels")),
+                }
+                Ok(())
+            }
+            TaskResult::StarChannel(channel_id, response) => {
+                match response {
+                    Ok(_) => self.state.set_channel_starred(&channel_id, true),
+                    Err(error) => self.state.add_channel_error_message(
+                        &channel_id,
+                        error.context("Could not star channel"),
+                    ),
+                }
+                Ok(())
+            }
+            TaskResult::UnstarChannel(channel_id, response) => {
+                match response {
+                    Ok(_) => self.state.set_channel_starred(&channel_id, false),
+                    Err(error) => self.state.add_channel_error_message(
+                        &channel_id,
+                        error.context("Could not unstar channel"),
+                    ),
+                }
+                Ok(())
+            }
         }
     }
 
-    fn accept_channel_history(
+    fn accept_stars(&mut self, response: slack::api::stars::ListResponse) {
+        use slack::api::stars::ListResponseItem;
+
+        // Starred messages and files are not shown anywhere yet.
+        for item in response.items.unwrap_or_default() {
+            match item {
+                ListResponseItem::Channel(item) => {
+                    self.state.set_channel_starred(&item.channel.into(), true)
+                }
+                ListResponseItem::Im(item) => {
+                    self.state.set_channel_starred(&item.channel.into(), true)
+                }
+                ListResponseItem::Group(item) => {
+                    self.state.set_channel_starred(&item.group.into(), true)
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn accept_thread_replies(
         &mut self,
         channel_id: ChannelID,
+        thread_id: MessageID,
+        messages: Vec<Value>,
+    ) {
+        let side_channel = MessageSideChannel {
+            channel_id: Some(channel_id.clone()),
+            ..Default::default()
+        };
+
+        // The response starts with the message that started the thread.
+        for message in messages.iter() {
+            match MessageEvent::from_json(message, &side_channel) {
+                Ok(Some(event)) => self.state.apply_message_event(event),
+                Ok(None) => {}
+                Err(error) => self.state.add_error_message(
+                    error.context("Could not convert Slack message to internal representation"),
+                ),
+            }
+        }
+
+        let reply_count = self
+            .state
+            .messages
+            .thread_iter(&channel_id, &thread_id)
+            .filter(|message| message.is_thread_reply())
+            .count();
+        self.state.apply_message_event(MessageEvent::Replied {
+            channel_id,
+            message_id: thread_id,
+            reply_count,
+        });
+    }
+
+    fn accept_joined_channel(
+        &mut self,
+        name: String,
         response: Result<
-            slack::api::channels::HistoryResponse,
-            slack::api::channels::HistoryError<slack::api::requests::Error>,
+            slack::api::channels::JoinResponse,
+            slack::api::channels::JoinError<slack::api::requests::Error>,
         >,
+    ) -> Result<(), Error> {
+        let channel = match response {
+            Ok(response) => response.channel.as_ref().and_then(Channel::from_slack),
+            Err(error) => {
+                self.state
+                    .add_error_message(error.context(format!("Could not join #{}", name)));
+                return Ok(());
+            }
+        };
+
+        if let Some(mut channel) = channel {
+            let id = channel.id().clone();
+            channel.set_is_member(true);
+            self.state.channels.add_channel(channel);
+            self.select_channel(id)?;
+        }
+        Ok(())
+    }
+
+    fn accept_channel_history(
+        &mut self,
+        channel_id: ChannelID,
+        response: Result<loader::History, Error>,
     ) -> Result<(), Error> {
         match response {
-            Ok(response) => {
-                if let Some(messages) = response.messages {
-                    let side_channel = MessageSideChannel {
-                        channel_id: Some(channel_id),
-                        ..Default::default()
-                    };
-
-                    for message in messages.into_iter() {
-                        match Message::from_slack_message(&message, &side_channel) {
-                            Ok(Some(message)) => self.state.add_message(message),
-                            Ok(None) => {}
-                            Err(error) => self.state.add_error_message(error.context(
+            Ok(history) => {
+                if history.has_more == Some(false) {
+                    self.state.add_channel_start(&channel_id);
+                }
+
+                let side_channel = MessageSideChannel {
+                    channel_id: Some(channel_id.clone()),
+                    ..Default::default()
+                };
+
+                for message in history.messages.iter() {
+                    match MessageEvent::from_json(message, &side_channel) {
+                        Ok(Some(event)) => self.state.apply_message_event(event),
+                        Ok(None) => {}
+                        Err(error) => self.state.add_channel_error_message(
+                            &channel_id,
+                            error.context(
                                 "Could not convert Slack message to internal representation",
-                            )),
-                        }
+                            ),
+                        ),
                     }
                 }
                 Ok(())
             }
             Err(error) => {
-                self.state
-                    .add_error_message(error.context("Could not load channel history"));
+                // Don't keep retrying pagination in a loop when Slack fails to respond. Selecting
+                // the channel again will retry.
+                self.state.add_channel_error_message(
+                    &channel_id,
+                    error.context("Could not load channel history"),
+                );
+                self.state.mark_history_load_failed(channel_id);
                 Ok(())
             }
         }
@@ -99,7 +99,7 @@ impl ChannelSelector {
         let mut matches: Vec<_> = channels
             .iter()
             .map(|(_, channel)| ChannelMatch {
-                score: calculate_score(&channel, &self.text),
+                score: calculate_score(channel.name(), &self.text),
                 channel,
             })
             .filter(|m| m.score > 0.0)
@@ -118,10 +118,11 @@ impl ChannelSelector {
     }
 }
 
-fn calculate_score(channel: &Channel, text: &str) -> f32 {
-    // Find first character from text in channel name, then search for second text character to the
+/// Scores how well a name matches the typed text. Names that don't match have negative scores.
+pub fn calculate_score(name: &str, text: &str) -> f32 {
+    // Find first character from text in the name, then search for second text character to the
     // right, and so on. If a character is not found, return a negative score.
-    let mut name = channel.name();
+    let mut name = name;
     for chr in text.chars() {
         match name.find(chr) {
             Some(pos) => {
@@ -285,4 +286,18 @@ mod tests {
         assert_eq!(&three_top_channels, &["foobar", "foosball"]);
         assert_eq!(&one_top_channels, &["foobar"]);
     }
+
+    #[test]
+    fn it_matches_direct_messages() {
+        let mut channel_list = ChannelList::new();
+        channel_list.add_channel(Channel::fixture("C1", "general"));
+        channel_list.add_channel(Channel::direct_message_fixture("D1", "gerald"));
+
+        let mut channel_selector = ChannelSelector::new();
+        channel_selector.add_character('a');
+        channel_selector.add_character('l');
+        channel_selector.add_character('d');
+
+        assert_eq!(channel_selector.select(&channel_list), Some("D1".into()));
+    }
 }
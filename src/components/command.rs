@@ -0,0 +1,198 @@
+use failure::Error;
+
+use components::completion::matching_names;
+use models::{emoji, ChannelList};
+
+/// A command entered in Command mode, like `:join general`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Join(String),
+    Leave,
+    Topic(String),
+    Purpose(String),
+    Open(String),
+    Mark,
+    /// Adds or removes a reaction on the selected message.
+    React(String),
+    Quit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Argument {
+    Nothing,
+    ChannelName,
+    EmojiName,
+    Text,
+}
+
+/// Available commands and the argument each of them takes.
+const COMMANDS: &[(&str, Argument)] = &[
+    ("join", Argument::ChannelName),
+    ("leave", Argument::Nothing),
+    ("mark", Argument::Nothing),
+    ("open", Argument::ChannelName),
+    ("purpose", Argument::Text),
+    ("quit", Argument::Nothing),
+    ("react", Argument::EmojiName),
+    ("topic", Argument::Text),
+];
+
+impl Command {
+    pub fn parse(text: &str) -> Result<Command, Error> {
+        let text = text.trim();
+        let (name, argument) = match text.find(char::is_whitespace) {
+            Some(index) => (&text[..index], text[index..].trim()),
+            None => (text, ""),
+        };
+
+        let expected_argument = match COMMANDS.iter().find(|&&(command, _)| command == name) {
+            Some(&(_, argument)) => argument,
+            None if name.is_empty() => return Err(format_err!("No command given")),
+            None => return Err(format_err!("Unknown command \"{}\"", name)),
+        };
+
+        match expected_argument {
+            Argument::Nothing if !argument.is_empty() => {
+                return Err(format_err!(":{} does not take any arguments", name))
+            }
+            Argument::ChannelName if argument.trim_left_matches('#').is_empty() => {
+                return Err(format_err!(":{} needs a channel name", name))
+            }
+            Argument::EmojiName if argument.trim_matches(':').is_empty() => {
+                return Err(format_err!(":{} needs an emoji name", name))
+            }
+            Argument::Text if argument.is_empty() => {
+                return Err(format_err!(":{} needs some text", name))
+            }
+            _ => {}
+        }
+
+        let channel_name = || argument.trim_left_matches('#').to_owned();
+        Ok(match name {
+            "join" => Command::Join(channel_name()),
+            "leave" => Command::Leave,
+            "mark" => Command::Mark,
+            "open" => Command::Open(channel_name()),
+            "purpose" => Command::Purpose(argument.to_owned()),
+            "quit" => Command::Quit,
+            "react" => Command::React(argument.trim_matches(':').to_owned()),
+            "topic" => Command::Topic(argument.to_owned()),
+            _ => unreachable!("All commands in COMMANDS are handled"),
+        })
+    }
+}
+
+/// Returns completion candidates for the word starting at `word_start` in a command line.
+///
+/// The first word completes to command names, and the argument of commands that take channels or
+/// emoji completes to their names.
+pub fn completions(line: &str, word_start: usize, channels: &ChannelList) -> Vec<String> {
+    let word = &line[word_start..];
+    let before_word = &line[..word_start];
+
+    if before_word.trim().is_empty() {
+        let names = COMMANDS.iter().map(|&(name, _)| name).collect();
+        return matching_names(names, word)
+            .into_iter()
+            .map(String::from)
+            .collect();
+    }
+
+    // Only the first argument can be completed.
+    let command_name = before_word.trim();
+    if command_name.contains(char::is_whitespace) {
+        return Vec::new();
+    }
+
+    match COMMANDS.iter().find(|&&(name, _)| name == command_name) {
+        Some(&(_, Argument::ChannelName)) => {
+            let names = channels.iter().map(|(_, channel)| channel.name()).collect();
+            matching_names(names, word.trim_left_matches('#'))
+                .into_iter()
+                .map(String::from)
+                .collect()
+        }
+        Some(&(_, Argument::EmojiName)) => {
+            matching_names(emoji::names().collect(), word.trim_matches(':'))
+                .into_iter()
+                .map(String::from)
+                .collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use models::Channel;
+
+    #[test]
+    fn it_parses_commands() {
+        assert_eq!(Command::parse("quit").unwrap(), Command::Quit);
+        assert_eq!(Command::parse(" leave ").unwrap(), Command::Leave);
+        assert_eq!(
+            Command::parse("join #general").unwrap(),
+            Command::Join(String::from("general"))
+        );
+        assert_eq!(
+            Command::parse("open random").unwrap(),
+            Command::Open(String::from("random"))
+        );
+        assert_eq!(
+            Command::parse("topic  Release  on Friday ").unwrap(),
+            Command::Topic(String::from("Release  on Friday"))
+        );
+        assert_eq!(
+            Command::parse("purpose Chatting").unwrap(),
+            Command::Purpose(String::from("Chatting"))
+        );
+        assert_eq!(Command::parse("mark").unwrap(), Command::Mark);
+        assert_eq!(
+            Command::parse("react :tada:").unwrap(),
+            Command::React(String::from("tada"))
+        );
+    }
+
+    #[test]
+    fn it_reports_invalid_commands() {
+        let error_for = |text: &str| Command::parse(text).unwrap_err().to_string();
+
+        assert_eq!(error_for(""), "No command given");
+        assert_eq!(error_for("explode now"), "Unknown command \"explode\"");
+        assert_eq!(error_for("quit now"), ":quit does not take any arguments");
+        assert_eq!(error_for("join #"), ":join needs a channel name");
+        assert_eq!(error_for("topic"), ":topic needs some text");
+        assert_eq!(error_for("react ::"), ":react needs an emoji name");
+    }
+
+    #[test]
+    fn it_completes_command_names() {
+        let channels = ChannelList::new();
+        assert_eq!(
+            completions("", 0, &channels),
+            &["join", "leave", "mark", "open", "purpose", "quit", "react", "topic"]
+        );
+        assert_eq!(completions("p", 0, &channels), &["purpose", "open", "topic"]);
+        assert_eq!(completions("qu", 0, &channels), &["quit"]);
+    }
+
+    #[test]
+    fn it_completes_channel_arguments() {
+        let mut channels = ChannelList::new();
+        channels.add_channel(Channel::fixture("C1", "general"));
+        channels.add_channel(Channel::fixture("C2", "random"));
+
+        assert_eq!(completions("join #ge", 5, &channels), &["general"]);
+        assert_eq!(completions("open ", 5, &channels), &["general", "random"]);
+        assert!(completions("topic ge", 6, &channels).is_empty());
+        assert!(completions("join general ra", 13, &channels).is_empty());
+    }
+
+    #[test]
+    fn it_completes_emoji_arguments() {
+        let channels = ChannelList::new();
+        assert_eq!(completions("react :tad", 6, &channels), &["tada"]);
+        assert_eq!(completions("react thumbsu", 6, &channels), &["thumbsup"]);
+    }
+}
@@ -0,0 +1,156 @@
+use components::channel_selector::calculate_score;
+use models::{emoji, ChannelList, UserList};
+
+/// An in-progress tab completion of a word in the composer or command line.
+///
+/// Each time a new candidate is selected, the word being completed is replaced with it, starting
+/// from `word_start`.
+#[derive(Debug)]
+pub struct Completion {
+    word_start: usize,
+    candidates: Vec<String>,
+    selected_index: usize,
+}
+
+impl Completion {
+    /// Starts a completion of `word`, if it is a `@user`, `#channel` or `:emoji` that has any
+    /// candidates.
+    pub fn start(
+        word_start: usize,
+        word: &str,
+        users: &UserList,
+        channels: &ChannelList,
+    ) -> Option<Completion> {
+        Completion::from_candidates(word_start, candidates(word, users, channels))
+    }
+
+    /// Starts a completion with the given candidates, unless there are none.
+    pub fn from_candidates(word_start: usize, candidates: Vec<String>) -> Option<Completion> {
+        if candidates.is_empty() {
+            None
+        } else {
+            Some(Completion {
+                word_start,
+                candidates,
+                selected_index: 0,
+            })
+        }
+    }
+
+    pub fn word_start(&self) -> usize {
+        self.word_start
+    }
+
+    pub fn candidates(&self) -> &[String] {
+        &self.candidates
+    }
+
+    pub fn selected_index(&self) -> usize {
+        self.selected_index
+    }
+
+    pub fn selected(&self) -> &str {
+        &self.candidates[self.selected_index]
+    }
+
+    pub fn select_next(&mut self) {
+        self.selected_index = (self.selected_index + 1) % self.candidates.len();
+    }
+}
+
+fn candidates(word: &str, users: &UserList, channels: &ChannelList) -> Vec<String> {
+    let mut chars = word.chars();
+    let sigil = match chars.next() {
+        Some(chr) => chr,
+        None => return Vec::new(),
+    };
+    let query = chars.as_str();
+
+    let names: Vec<&str> = match sigil {
+        '@' => users.iter().map(|(_, user)| user.display_name()).collect(),
+        '#' => channels
+            .iter()
+            .filter(|&(_, channel)| !channel.kind().is_direct_message())
+            .map(|(_, channel)| channel.name())
+            .collect(),
+        ':' => emoji::names().collect(),
+        _ => return Vec::new(),
+    };
+
+    matching_names(names, query)
+        .into_iter()
+        .map(|name| match sigil {
+            ':' => format!(":{}:", name),
+            _ => format!("{}{}", sigil, name),
+        })
+        .collect()
+}
+
+/// Filters names that match the query, sorted by how likely they are to be what the user wants.
+pub fn matching_names<'a>(names: Vec<&'a str>, query: &str) -> Vec<&'a str> {
+    let mut matches: Vec<&str> = names
+        .into_iter()
+        .filter(|name| calculate_score(name, query) > 0.0)
+        .collect();
+    // Names starting with the query are most likely what the user is looking for.
+    matches.sort_by_key(|name| (!name.starts_with(query), *name));
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use models::{Channel, User};
+
+    fn start(word: &str) -> Option<Completion> {
+        let mut users = UserList::new();
+        users.add_user(User::fixture("U1", "alice"));
+        users.add_user(User::fixture("U2", "malin"));
+        users.add_user(User::fixture("U3", "bob"));
+
+        let mut channels = ChannelList::new();
+        channels.add_channel(Channel::fixture("C1", "general"));
+        channels.add_channel(Channel::fixture("C2", "random"));
+        channels.add_channel(Channel::direct_message_fixture("D1", "gerald"));
+
+        Completion::start(0, word, &users, &channels)
+    }
+
+    #[test]
+    fn it_completes_users_with_prefix_matches_first() {
+        let completion = start("@al").unwrap();
+        assert_eq!(completion.candidates(), &["@alice", "@malin"]);
+    }
+
+    #[test]
+    fn it_completes_channels() {
+        let completion = start("#ge").unwrap();
+        assert_eq!(completion.candidates(), &["#general"]);
+    }
+
+    #[test]
+    fn it_completes_emoji() {
+        let completion = start(":thumbs").unwrap();
+        assert_eq!(completion.candidates(), &[":thumbsdown:", ":thumbsup:"]);
+    }
+
+    #[test]
+    fn it_does_not_complete_other_words() {
+        assert!(start("hello").is_none());
+        assert!(start("").is_none());
+        assert!(start("@nobody").is_none());
+    }
+
+    #[test]
+    fn it_cycles_through_candidates() {
+        let mut completion = start("@").unwrap();
+        assert_eq!(completion.selected(), "@alice");
+
+        completion.select_next();
+        assert_eq!(completion.selected(), "@bob");
+
+        completion.select_next();
+        completion.select_next();
+        assert_eq!(completion.selected(), "@alice");
+    }
+}
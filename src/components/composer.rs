@@ -0,0 +1,680 @@
+/// Multi-line text buffer for composing replies.
+///
+/// The cursor position is stored as a byte offset into the text and is always kept on a character
+/// boundary.
+///
+/// Editing is modelled after Vim; in addition to the basic editing used in Insert mode there are
+/// motions and edits for Normal mode. Normal mode edits and Insert mode sessions are recorded as
+/// single steps in the undo history.
+#[derive(Debug)]
+pub struct Composer {
+    text: String,
+    cursor_pos: usize,
+    undo_stack: Vec<Snapshot>,
+    redo_stack: Vec<Snapshot>,
+}
+
+#[derive(Debug, Clone)]
+struct Snapshot {
+    text: String,
+    cursor_pos: usize,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum CharClass {
+    Whitespace,
+    Word,
+    Punctuation,
+}
+
+impl Composer {
+    pub fn new() -> Self {
+        Composer {
+            text: String::new(),
+            cursor_pos: 0,
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn cursor_pos(&self) -> usize {
+        self.cursor_pos
+    }
+
+    pub fn is_blank(&self) -> bool {
+        self.text.trim().is_empty()
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.text.split('\n').count()
+    }
+
+    /// Returns the (line, column) of the cursor, where the column is counted in characters.
+    pub fn cursor_line_and_column(&self) -> (usize, usize) {
+        let before_cursor = &self.text[..self.cursor_pos];
+        let line = before_cursor.matches('\n').count();
+        let column = before_cursor[self.line_start()..].chars().count();
+        (line, column)
+    }
+
+    /// Returns the byte offset and text of the word leading up to the cursor.
+    pub fn word_before_cursor(&self) -> (usize, &str) {
+        let start = self.text[..self.cursor_pos]
+            .rfind(|c| c == ' ' || c == '\n')
+            .map(|pos| pos + 1)
+            .unwrap_or(0);
+        (start, &self.text[start..self.cursor_pos])
+    }
+
+    /// Replaces the text between `start` and the cursor, placing the cursor after the replacement.
+    pub fn replace_before_cursor(&mut self, start: usize, replacement: &str) {
+        self.text.drain(start..self.cursor_pos);
+        self.text.insert_str(start, replacement);
+        self.cursor_pos = start + replacement.len();
+    }
+
+    pub fn reset(&mut self) {
+        self.text.clear();
+        self.cursor_pos = 0;
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+    }
+
+    /// Replaces the buffer with `text`, placing the cursor at the end.
+    pub fn set_text(&mut self, text: String) {
+        self.reset();
+        self.cursor_pos = text.len();
+        self.text = text;
+    }
+
+    /// Clears the buffer and returns the text that was in it.
+    pub fn take_text(&mut self) -> String {
+        let text = ::std::mem::replace(&mut self.text, String::new());
+        self.reset();
+        text
+    }
+
+    pub fn add_character(&mut self, chr: char) {
+        self.text.insert(self.cursor_pos, chr);
+        self.cursor_pos += chr.len_utf8();
+    }
+
+    pub fn delete_character(&mut self) {
+        if let Some(previous) = self.previous_boundary() {
+            self.text.remove(previous);
+            self.cursor_pos = previous;
+        }
+    }
+
+    pub fn delete_word(&mut self) {
+        let line_start = self.line_start();
+        let before_cursor = &self.text[line_start..self.cursor_pos];
+        let word_start = before_cursor
+            .trim_right()
+            .rfind(' ')
+            .map(|pos| line_start + pos + 1)
+            .unwrap_or(line_start);
+
+        self.text.drain(word_start..self.cursor_pos);
+        self.cursor_pos = word_start;
+    }
+
+    pub fn move_cursor_left(&mut self) {
+        if let Some(previous) = self.previous_boundary() {
+            self.cursor_pos = previous;
+        }
+    }
+
+    pub fn move_cursor_right(&mut self) {
+        if let Some(chr) = self.text[self.cursor_pos..].chars().next() {
+            self.cursor_pos += chr.len_utf8();
+        }
+    }
+
+    pub fn move_cursor_up(&mut self) {
+        let line_start = self.line_start();
+        if line_start > 0 {
+            let (_, column) = self.cursor_line_and_column();
+            self.cursor_pos = line_start - 1;
+            self.move_to_column(column);
+        }
+    }
+
+    pub fn move_cursor_down(&mut self) {
+        let line_end = self.line_end();
+        if line_end < self.text.len() {
+            let (_, column) = self.cursor_line_and_column();
+            self.cursor_pos = line_end + 1;
+            self.move_to_column(column);
+        }
+    }
+
+    pub fn move_to_beginning(&mut self) {
+        self.cursor_pos = self.line_start();
+    }
+
+    pub fn move_to_end(&mut self) {
+        self.cursor_pos = self.line_end();
+    }
+
+    //
+    // Normal mode
+    //
+
+    /// Records the current state as an undo step before an Insert mode session starts.
+    pub fn begin_insert(&mut self) {
+        self.checkpoint();
+    }
+
+    /// Moves the cursor onto the last character, like Vim does when leaving Insert mode.
+    pub fn end_insert(&mut self) {
+        if self.cursor_pos > self.line_start() {
+            self.move_cursor_left();
+        }
+    }
+
+    pub fn move_left_in_line(&mut self) {
+        if self.cursor_pos > self.line_start() {
+            self.move_cursor_left();
+        }
+    }
+
+    pub fn move_right_in_line(&mut self) {
+        self.move_cursor_right();
+        self.clamp_to_line();
+    }
+
+    pub fn move_up_in_normal_mode(&mut self) {
+        self.move_cursor_up();
+        self.clamp_to_line();
+    }
+
+    pub fn move_down_in_normal_mode(&mut self) {
+        self.move_cursor_down();
+        self.clamp_to_line();
+    }
+
+    pub fn move_to_last_character(&mut self) {
+        self.move_to_end();
+        self.clamp_to_line();
+    }
+
+    pub fn move_to_first_line(&mut self) {
+        self.cursor_pos = 0;
+    }
+
+    pub fn move_to_last_line(&mut self) {
+        self.cursor_pos = self.text.rfind('\n').map(|pos| pos + 1).unwrap_or(0);
+    }
+
+    /// Moves to the start of the next word, like `w` in Vim.
+    pub fn move_word_forward(&mut self) {
+        self.cursor_pos = self.next_word_start();
+        self.clamp_to_line();
+    }
+
+    /// Moves to the start of the current or previous word, like `b` in Vim.
+    pub fn move_word_backward(&mut self) {
+        let before: Vec<(usize, char)> = self.text[..self.cursor_pos].char_indices().collect();
+        let mut i = before.len();
+
+        while i > 0 && char_class(before[i - 1].1) == CharClass::Whitespace {
+            i -= 1;
+        }
+        if let Some(&(_, chr)) = before.get(i.saturating_sub(1)) {
+            let class = char_class(chr);
+            while i > 0 && char_class(before[i - 1].1) == class {
+                i -= 1;
+            }
+        }
+
+        self.cursor_pos = before.get(i).map(|&(pos, _)| pos).unwrap_or(0);
+    }
+
+    /// Deletes the character under the cursor, like `x` in Vim.
+    pub fn delete_character_under_cursor(&mut self) {
+        if self.cursor_pos < self.line_end() {
+            self.checkpoint();
+            self.text.remove(self.cursor_pos);
+            self.clamp_to_line();
+        }
+    }
+
+    /// Deletes the current line, like `dd` in Vim.
+    pub fn delete_line(&mut self) {
+        self.checkpoint();
+        let line_start = self.line_start();
+        let line_end = self.line_end();
+
+        if line_end < self.text.len() {
+            // Remove the line and the newline after it; the next line takes its place.
+            self.text.drain(line_start..line_end + 1);
+            self.cursor_pos = line_start;
+        } else if line_start > 0 {
+            // Last line; remove the newline before it and place cursor on the previous line.
+            self.text.drain(line_start - 1..line_end);
+            self.cursor_pos = line_start - 1;
+            self.cursor_pos = self.line_start();
+        } else {
+            self.text.clear();
+            self.cursor_pos = 0;
+        }
+    }
+
+    /// Deletes to the start of the next word on the same line, like `dw` in Vim.
+    pub fn delete_word_forward(&mut self) {
+        let end = self.next_word_start().min(self.line_end());
+        if end > self.cursor_pos {
+            self.checkpoint();
+            self.text.drain(self.cursor_pos..end);
+            self.clamp_to_line();
+        }
+    }
+
+    /// Deletes to the end of the current word so it can be replaced, like `cw` in Vim.
+    ///
+    /// The cursor is left where the word used to be, ready for Insert mode.
+    pub fn change_word(&mut self) {
+        self.checkpoint();
+        let end = match self.text[self.cursor_pos..].chars().next() {
+            Some(chr) => {
+                let class = char_class(chr);
+                self.text[self.cursor_pos..]
+                    .char_indices()
+                    .find(|&(_, c)| char_class(c) != class || c == '\n')
+                    .map(|(offset, _)| self.cursor_pos + offset)
+                    .unwrap_or_else(|| self.text.len())
+            }
+            None => self.cursor_pos,
+        };
+        self.text.drain(self.cursor_pos..end);
+    }
+
+    /// Opens a new line below the current one, like `o` in Vim.
+    pub fn open_line_below(&mut self) {
+        self.checkpoint();
+        self.cursor_pos = self.line_end();
+        self.add_character('\n');
+    }
+
+    /// Opens a new line above the current one, like `O` in Vim.
+    pub fn open_line_above(&mut self) {
+        self.checkpoint();
+        self.cursor_pos = self.line_start();
+        self.text.insert(self.cursor_pos, '\n');
+    }
+
+    pub fn undo(&mut self) {
+        if let Some(snapshot) = self.undo_stack.pop() {
+            let current = self.snapshot();
+            self.redo_stack.push(current);
+            self.restore(snapshot);
+        }
+    }
+
+    pub fn redo(&mut self) {
+        if let Some(snapshot) = self.redo_stack.pop() {
+            let current = self.snapshot();
+            self.undo_stack.push(current);
+            self.restore(snapshot);
+        }
+    }
+
+    fn checkpoint(&mut self) {
+        let snapshot = self.snapshot();
+        self.undo_stack.push(snapshot);
+        self.redo_stack.clear();
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            text: self.text.clone(),
+            cursor_pos: self.cursor_pos,
+        }
+    }
+
+    fn restore(&mut self, snapshot: Snapshot) {
+        self.text = snapshot.text;
+        self.cursor_pos = snapshot.cursor_pos;
+    }
+
+    /// Keeps the cursor on a character in Normal mode, rather than after the end of the line.
+    fn clamp_to_line(&mut self) {
+        if self.cursor_pos == self.line_end() && self.cursor_pos > self.line_start() {
+            self.move_cursor_left();
+        }
+    }
+
+    fn next_word_start(&self) -> usize {
+        let rest = &self.text[self.cursor_pos..];
+        let start_class = match rest.chars().next() {
+            Some(chr) => char_class(chr),
+            None => return self.cursor_pos,
+        };
+
+        let mut seen_whitespace = false;
+        for (offset, chr) in rest.char_indices() {
+            let class = char_class(chr);
+            if class == CharClass::Whitespace {
+                seen_whitespace = true;
+            } else if seen_whitespace || class != start_class {
+                return self.cursor_pos + offset;
+            }
+        }
+        self.text.len()
+    }
+
+    fn move_to_column(&mut self, column: usize) {
+        let line_start = self.line_start();
+        let line_end = self.line_end();
+        self.cursor_pos = self.text[line_start..line_end]
+            .char_indices()
+            .nth(column)
+            .map(|(pos, _)| line_start + pos)
+            .unwrap_or(line_end);
+    }
+
+    fn previous_boundary(&self) -> Option<usize> {
+        self.text[..self.cursor_pos]
+            .char_indices()
+            .next_back()
+            .map(|(pos, _)| pos)
+    }
+
+    fn line_start(&self) -> usize {
+        self.text[..self.cursor_pos]
+            .rfind('\n')
+            .map(|pos| pos + 1)
+            .unwrap_or(0)
+    }
+
+    fn line_end(&self) -> usize {
+        self.text[self.cursor_pos..]
+            .find('\n')
+            .map(|pos| self.cursor_pos + pos)
+            .unwrap_or_else(|| self.text.len())
+    }
+}
+
+fn char_class(chr: char) -> CharClass {
+    if chr.is_whitespace() {
+        CharClass::Whitespace
+    } else if chr.is_alphanumeric() || chr == '_' {
+        CharClass::Word
+    } else {
+        CharClass::Punctuation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn composer_with_text(text: &str) -> Composer {
+        let mut composer = Composer::new();
+        for chr in text.chars() {
+            composer.add_character(chr);
+        }
+        composer
+    }
+
+    #[test]
+    fn it_adds_characters_and_newlines() {
+        let composer = composer_with_text("foo\nbar");
+
+        assert_eq!(composer.text(), "foo\nbar");
+        assert_eq!(composer.line_count(), 2);
+        assert_eq!(composer.cursor_line_and_column(), (1, 3));
+    }
+
+    #[test]
+    fn it_deletes_multibyte_characters() {
+        let mut composer = composer_with_text("häj");
+        composer.move_cursor_left();
+        composer.delete_character();
+
+        assert_eq!(composer.text(), "hj");
+        assert_eq!(composer.cursor_line_and_column(), (0, 1));
+    }
+
+    #[test]
+    fn it_deletes_word_on_current_line() {
+        let mut composer = composer_with_text("foo\nbar baz ");
+        composer.delete_word();
+        assert_eq!(composer.text(), "foo\nbar ");
+
+        composer.delete_word();
+        assert_eq!(composer.text(), "foo\n");
+    }
+
+    #[test]
+    fn it_moves_between_lines_keeping_column() {
+        let mut composer = composer_with_text("a long line\nab\nanother line");
+        composer.move_cursor_up();
+        assert_eq!(composer.cursor_line_and_column(), (1, 2));
+
+        composer.move_cursor_up();
+        assert_eq!(composer.cursor_line_and_column(), (0, 2));
+
+        composer.move_cursor_up();
+        assert_eq!(composer.cursor_line_and_column(), (0, 2));
+
+        composer.move_cursor_down();
+        composer.move_cursor_down();
+        assert_eq!(composer.cursor_line_and_column(), (2, 2));
+    }
+
+    #[test]
+    fn it_moves_to_beginning_and_end_of_line() {
+        let mut composer = composer_with_text("foo\nbar");
+        composer.move_to_beginning();
+        composer.add_character('>');
+        composer.move_cursor_up();
+        composer.move_to_end();
+        composer.add_character('!');
+
+        assert_eq!(composer.text(), "foo!\n>bar");
+    }
+
+    #[test]
+    fn it_replaces_word_before_cursor() {
+        let mut composer = composer_with_text("hi @al there");
+        for _ in 0.." there".len() {
+            composer.move_cursor_left();
+        }
+
+        let (start, word) = composer.word_before_cursor();
+        assert_eq!((start, word), (3, "@al"));
+
+        composer.replace_before_cursor(start, "@alice");
+        composer.add_character('!');
+        assert_eq!(composer.text(), "hi @alice! there");
+    }
+
+    #[test]
+    fn it_takes_text_and_resets() {
+        let mut composer = composer_with_text("Hello");
+
+        assert_eq!(composer.take_text(), "Hello");
+        assert_eq!(composer.text(), "");
+        assert_eq!(composer.cursor_pos(), 0);
+        assert!(composer.is_blank());
+    }
+
+    #[test]
+    fn it_sets_text_with_cursor_at_end() {
+        let mut composer = composer_with_text("Hello");
+
+        composer.set_text(String::from("Héllo\nworld"));
+        assert_eq!(composer.text(), "Héllo\nworld");
+        assert_eq!(composer.cursor_line_and_column(), (1, 5));
+        composer.undo();
+        assert_eq!(composer.text(), "Héllo\nworld");
+    }
+
+    /// Builds a composer in the state it would be after typing the text in Insert mode and
+    /// pressing Esc.
+    fn normal_composer(text: &str) -> Composer {
+        let mut composer = composer_with_text(text);
+        composer.end_insert();
+        composer
+    }
+
+    fn char_under_cursor(composer: &Composer) -> Option<char> {
+        composer.text()[composer.cursor_pos()..].chars().next()
+    }
+
+    #[test]
+    fn it_keeps_cursor_on_characters_in_normal_mode() {
+        let mut composer = normal_composer("abc\nde");
+        assert_eq!(char_under_cursor(&composer), Some('e'));
+
+        composer.move_right_in_line();
+        assert_eq!(char_under_cursor(&composer), Some('e'));
+
+        composer.move_up_in_normal_mode();
+        assert_eq!(char_under_cursor(&composer), Some('b'));
+
+        composer.move_to_last_character();
+        assert_eq!(char_under_cursor(&composer), Some('c'));
+
+        composer.move_down_in_normal_mode();
+        assert_eq!(char_under_cursor(&composer), Some('e'));
+
+        composer.move_to_beginning();
+        composer.move_left_in_line();
+        assert_eq!(char_under_cursor(&composer), Some('d'));
+    }
+
+    #[test]
+    fn it_moves_by_words() {
+        let mut composer = normal_composer("foo.bar  baz\nqux");
+        composer.move_to_first_line();
+
+        composer.move_word_forward();
+        assert_eq!(composer.cursor_line_and_column(), (0, 3));
+        composer.move_word_forward();
+        assert_eq!(composer.cursor_line_and_column(), (0, 4));
+        composer.move_word_forward();
+        assert_eq!(composer.cursor_line_and_column(), (0, 9));
+        composer.move_word_forward();
+        assert_eq!(composer.cursor_line_and_column(), (1, 0));
+
+        composer.move_word_backward();
+        assert_eq!(composer.cursor_line_and_column(), (0, 9));
+        composer.move_word_backward();
+        assert_eq!(composer.cursor_line_and_column(), (0, 4));
+        composer.move_word_backward();
+        composer.move_word_backward();
+        assert_eq!(composer.cursor_line_and_column(), (0, 0));
+        composer.move_word_backward();
+        assert_eq!(composer.cursor_line_and_column(), (0, 0));
+    }
+
+    #[test]
+    fn it_moves_to_first_and_last_line() {
+        let mut composer = normal_composer("one\ntwo\nthree");
+        composer.move_to_first_line();
+        assert_eq!(composer.cursor_line_and_column(), (0, 0));
+
+        composer.move_to_last_line();
+        assert_eq!(composer.cursor_line_and_column(), (2, 0));
+    }
+
+    #[test]
+    fn it_deletes_character_under_cursor() {
+        let mut composer = normal_composer("abc");
+        composer.delete_character_under_cursor();
+        assert_eq!(composer.text(), "ab");
+        assert_eq!(char_under_cursor(&composer), Some('b'));
+
+        composer.move_to_beginning();
+        composer.delete_character_under_cursor();
+        assert_eq!(composer.text(), "b");
+    }
+
+    #[test]
+    fn it_deletes_lines() {
+        let mut composer = normal_composer("one\ntwo\nthree");
+        composer.move_up_in_normal_mode();
+        composer.delete_line();
+        assert_eq!(composer.text(), "one\nthree");
+        assert_eq!(composer.cursor_line_and_column(), (1, 0));
+
+        composer.delete_line();
+        assert_eq!(composer.text(), "one");
+        assert_eq!(composer.cursor_line_and_column(), (0, 0));
+
+        composer.delete_line();
+        assert_eq!(composer.text(), "");
+    }
+
+    #[test]
+    fn it_deletes_words_forward() {
+        let mut composer = normal_composer("foo bar\nbaz");
+        composer.move_to_first_line();
+        composer.delete_word_forward();
+        assert_eq!(composer.text(), "bar\nbaz");
+
+        // Does not join lines
+        composer.delete_word_forward();
+        assert_eq!(composer.text(), "\nbaz");
+    }
+
+    #[test]
+    fn it_changes_words() {
+        let mut composer = normal_composer("foo bar baz");
+        composer.move_to_first_line();
+        composer.move_word_forward();
+        composer.change_word();
+        for chr in "qux".chars() {
+            composer.add_character(chr);
+        }
+
+        assert_eq!(composer.text(), "foo qux baz");
+    }
+
+    #[test]
+    fn it_opens_lines() {
+        let mut composer = normal_composer("one\ntwo");
+        composer.move_up_in_normal_mode();
+        composer.open_line_below();
+        composer.add_character('a');
+        assert_eq!(composer.text(), "one\na\ntwo");
+
+        composer.open_line_above();
+        composer.add_character('b');
+        assert_eq!(composer.text(), "one\nb\na\ntwo");
+    }
+
+    #[test]
+    fn it_undoes_and_redoes_edits() {
+        let mut composer = normal_composer("foo");
+        composer.begin_insert();
+        composer.add_character('!');
+        composer.add_character('!');
+        composer.end_insert();
+        composer.delete_line();
+        assert_eq!(composer.text(), "");
+
+        composer.undo();
+        assert_eq!(composer.text(), "fo!!o");
+        composer.undo();
+        assert_eq!(composer.text(), "foo");
+        composer.undo();
+        assert_eq!(composer.text(), "foo");
+
+        composer.redo();
+        composer.redo();
+        assert_eq!(composer.text(), "");
+
+        composer.undo();
+        composer.delete_character_under_cursor();
+        composer.redo();
+        assert_eq!(composer.text(), "fo!o");
+    }
+}
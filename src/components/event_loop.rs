@@ -1,15 +1,20 @@
 extern crate slack;
 
-use failure::{Error, Fail};
+use failure::{Error, Fail, ResultExt};
+use serde_json::{self, Value};
+use slack::api::stars;
 use std::io;
 use std::sync::mpsc;
 use std::thread;
 use std::time::Duration;
 use termion::event::Key;
 use termion::input::TermRead;
+use tungstenite::stream::Stream;
+use tungstenite::{self, Error as WsError, Message as WsMessage};
+use url::Url;
 
-use components::{input_manager, App, KeyManager};
-use models::Message;
+use components::{input_manager, App, KeyManager, Keymap};
+use models::{ChannelID, MessageEvent, MessageID, MessageSideChannel};
 use TerminalBackend;
 
 #[derive(Debug)]
@@ -19,36 +24,94 @@ pub enum Event {
     Input(Key),
     Connected,
     Disconnected,
-    Message(Box<Message>),
+    Message(Box<MessageEvent>),
+    ChannelMarked(ChannelID, MessageID),
+    ChannelStarred(ChannelID, bool),
 }
 
 struct SlackEventHandler {
     tx: mpsc::Sender<Event>,
 }
 
-impl slack::EventHandler for SlackEventHandler {
-    fn on_connect(&mut self, _rtm: &slack::RtmClient) {
-        let _ = self.tx.send(Event::Connected);
-    }
+impl SlackEventHandler {
+    /// Reads events from the RTM connection until it fails.
+    ///
+    /// `slack::RtmClient::run` silently drops every event it cannot parse, which includes all
+    /// reaction events, so the websocket is read here to keep the JSON around.
+    fn run(&mut self, url: &str) -> Result<(), Error> {
+        let url = Url::parse(url).context("Slack sent an invalid RTM URL")?;
+        let (mut websocket, _) = tungstenite::connect(url)?;
+
+        // Slack can leave the connection hanging, so ping it when it has been quiet for a while.
+        match *websocket.get_mut() {
+            Stream::Plain(ref socket) => socket.set_read_timeout(Some(Duration::from_secs(30)))?,
+            Stream::Tls(ref mut socket) => socket
+                .get_mut()
+                .set_read_timeout(Some(Duration::from_secs(30)))?,
+        }
+
+        self.tx.send(Event::Connected)?;
 
-    fn on_close(&mut self, _rtm: &slack::RtmClient) {
-        let _ = self.tx.send(Event::Disconnected);
+        loop {
+            match websocket.read_message() {
+                Ok(WsMessage::Text(text)) => {
+                    if let Err(error) = self.handle_json(&text) {
+                        self.tx.send(Event::Error(Box::new(error)))?;
+                    }
+                }
+                Ok(_) => {}
+                Err(WsError::Io(ref error))
+                    if error.kind() == io::ErrorKind::WouldBlock
+                        || error.kind() == io::ErrorKind::TimedOut =>
+                {
+                    websocket.write_message(WsMessage::Ping(vec![]))?;
+                }
+                Err(WsError::ConnectionClosed(_)) => {
+                    self.tx.send(Event::Disconnected)?;
+                    return Ok(());
+                }
+                Err(error) => return Err(error.into()),
+            }
+        }
     }
 
-    fn on_event(&mut self, _rtm: &slack::RtmClient, slack_event: slack::Event) {
-        match self.handle_event(slack_event) {
-            Ok(_) => {}
-            Err(error) => {
-                self.tx.send(Event::Error(Box::new(error))).ok();
+    fn handle_json(&mut self, text: &str) -> Result<(), Error> {
+        let json: Value = serde_json::from_str(text)?;
+        // slack::Event cannot parse reaction events, nor replies that were also sent to the
+        // channel.
+        let event = match json.get("type").and_then(Value::as_str) {
+            Some("reaction_added") | Some("reaction_removed") => {
+                MessageEvent::from_reaction_json(&json)?
             }
+            Some("message") if is_broadcast_reply(&json) => {
+                MessageEvent::from_json(&json, &MessageSideChannel::default())?
+            }
+            // Like slack::RtmClient, ignore events that slack::Event does not know.
+            _ => {
+                return match serde_json::from_value(json) {
+                    Ok(slack_event) => self.handle_event(slack_event),
+                    Err(_) => Ok(()),
+                }
+            }
+        };
+
+        if let Some(event) = event {
+            self.tx.send(Event::Message(Box::new(event)))?;
         }
+        Ok(())
     }
-}
 
-impl SlackEventHandler {
     fn handle_event(&mut self, slack_event: slack::Event) -> Result<(), Error> {
         match slack_event {
             slack::Event::Message(msg) => self.new_message(*msg)?,
+            slack::Event::ChannelMarked { channel, ts }
+            | slack::Event::GroupMarked { channel, ts }
+            | slack::Event::ImMarked { channel, ts } => {
+                self.tx
+                    .send(Event::ChannelMarked(channel.into(), ts.into()))?
+            }
+            slack::Event::StarAdded { item, .. } => self.star_changed(*item, true)?,
+            slack::Event::StarRemoved { item, .. } => self.star_changed(*item, false)?,
             _ => {}
         }
 
@@ -56,18 +119,39 @@ impl SlackEventHandler {
     }
 
     fn new_message(&mut self, msg: slack::Message) -> Result<(), Error> {
-        match Message::from_slack_message(&msg, None)? {
-            Some(message) => self.tx.send(Event::Message(Box::new(message)))?,
+        match MessageEvent::from_slack_message(&msg, None)? {
+            Some(event) => self.tx.send(Event::Message(Box::new(event)))?,
             None => {}
         }
         Ok(())
     }
+
+    fn star_changed(
+        &mut self,
+        item: stars::ListResponseItem,
+        is_starred: bool,
+    ) -> Result<(), Error> {
+        let channel_id = match item {
+            stars::ListResponseItem::Channel(item) => item.channel,
+            stars::ListResponseItem::Group(item) => item.group,
+            stars::ListResponseItem::Im(item) => item.channel,
+            _ => return Ok(()),
+        };
+        self.tx
+            .send(Event::ChannelStarred(channel_id.into(), is_starred))?;
+        Ok(())
+    }
+}
+
+fn is_broadcast_reply(json: &Value) -> bool {
+    json.get("subtype").and_then(Value::as_str) == Some("thread_broadcast")
 }
 
 pub fn run(
     app: &mut App,
     rtm: slack::RtmClient,
     terminal: &mut TerminalBackend,
+    keymap: Keymap,
 ) -> Result<(), Error> {
     let (tx, rx) = mpsc::channel();
     let input_tx = tx.clone();
@@ -94,14 +178,24 @@ pub fn run(
         thread::sleep(Duration::from_millis(200));
     });
 
+    let rtm_url = rtm
+        .start_response()
+        .url
+        .clone()
+        .ok_or_else(|| format_err!("Slack did not provide an RTM URL"))?;
     let slack_tx = tx.clone();
     thread::spawn(move || {
-        rtm.run(&mut SlackEventHandler { tx: slack_tx });
+        let mut handler = SlackEventHandler {
+            tx: slack_tx.clone(),
+        };
+        if let Err(error) = handler.run(&rtm_url) {
+            slack_tx.send(Event::Error(Box::new(error))).ok();
+        }
     });
 
     // TODO: Move to App; but then KeyManager cannot take &mut of App anymore. Instead, give an
     // action enum back to the app so it can act on its own(?).
-    let mut key_manager = KeyManager::new();
+    let mut key_manager = KeyManager::new(keymap);
 
     loop {
         // Deal with new size, if resized
@@ -114,6 +208,14 @@ pub fn run(
         // Draw the App component to the terminal
         app.draw(terminal)?;
 
+        // Drawing updates the rendered history, so now is the time to act on the scroll position:
+        // load older messages near the top, jump to new messages or the selected message, and mark
+        // what the user has seen.
+        app.load_older_history_if_needed()?;
+        app.state_mut().scroll_to_new_messages_if_needed();
+        app.state_mut().scroll_to_selected_message_if_needed();
+        app.mark_selected_channel_if_needed()?;
+
         // Handle any pending data (not blocking)
         if let Some(task_result) = app.loader_mut().pending_result() {
             app.accept_task_result(task_result)?;
@@ -134,8 +236,52 @@ pub fn run(
                     "Slack disconnected. Offline mode is not yet implemented"
                 ));
             }
-            Event::Message(message) => app.state_mut().add_message(*message),
-            Event::Tick => {}
+            Event::Message(event) => app.state_mut().receive_message_event(*event),
+            Event::ChannelMarked(channel_id, message_id) => app
+                .state_mut()
+                .accept_channel_marked(channel_id, message_id),
+            Event::ChannelStarred(channel_id, is_starred) => app
+                .state_mut()
+                .set_channel_starred(&channel_id, is_starred),
+            Event::Tick => key_manager.tick(app),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use models::Message;
+
+    fn handle_json(json: &str) -> Vec<Event> {
+        let (tx, rx) = mpsc::channel();
+        SlackEventHandler { tx }.handle_json(json).unwrap();
+        rx.try_iter().collect()
+    }
+
+    #[test]
+    fn it_reads_broadcast_replies() {
+        let events = handle_json(
+            r#"{"type": "message", "subtype": "thread_broadcast", "channel": "C1",
+                "user": "U1", "text": "Yes!", "ts": "1112.0", "thread_ts": "1111.0",
+                "root": {"type": "message", "user": "U2", "text": "Lunch?", "ts": "1111.0"},
+                "event_ts": "1112.0"}"#,
+        );
+
+        match events.as_slice() {
+            [Event::Message(event)] => match **event {
+                MessageEvent::New(Message::Standard(ref message)) => {
+                    assert_eq!(message.channel_id, ChannelID::from("C1"));
+                    assert!(message.is_broadcast);
+                }
+                ref other => panic!("Unexpected event: {:?}", other),
+            },
+            other => panic!("Unexpected events: {:?}", other),
         }
     }
+
+    #[test]
+    fn it_ignores_unknown_events() {
+        assert!(handle_json(r#"{"type": "dnd_updated_user", "user": "U1"}"#).is_empty());
+    }
 }
@@ -0,0 +1,104 @@
+use std::cell::Cell;
+
+/// The key bindings of a mode, shown in an overlay until any key other than a scrolling one is
+/// pressed.
+#[derive(Debug)]
+pub struct Help {
+    entries: Vec<(String, &'static str)>,
+    scroll: usize,
+    /// Number of entries that fit in the overlay when it was last rendered.
+    page_height: Cell<usize>,
+}
+
+impl Help {
+    pub fn new(entries: Vec<(String, &'static str)>) -> Help {
+        let page_height = Cell::new(entries.len());
+        Help {
+            entries,
+            scroll: 0,
+            page_height,
+        }
+    }
+
+    pub fn entries(&self) -> &[(String, &'static str)] {
+        &self.entries
+    }
+
+    /// Returns the index of the first entry shown.
+    pub fn scroll(&self) -> usize {
+        self.scroll.min(self.max_scroll())
+    }
+
+    pub fn page_height(&self) -> usize {
+        self.page_height.get()
+    }
+
+    pub fn set_page_height(&self, height: usize) {
+        self.page_height.set(height);
+    }
+
+    /// Returns true if some entries do not fit in the overlay.
+    pub fn is_scrollable(&self) -> bool {
+        self.max_scroll() > 0
+    }
+
+    pub fn scroll_down(&mut self, lines: usize) {
+        self.scroll = (self.scroll() + lines).min(self.max_scroll());
+    }
+
+    pub fn scroll_up(&mut self, lines: usize) {
+        self.scroll = self.scroll().saturating_sub(lines);
+    }
+
+    fn max_scroll(&self) -> usize {
+        self.entries.len().saturating_sub(self.page_height.get())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn help_with_entries(count: usize) -> Help {
+        Help::new((0..count).map(|i| (i.to_string(), "Action")).collect())
+    }
+
+    #[test]
+    fn it_scrolls_within_the_entries() {
+        let mut help = help_with_entries(25);
+        help.set_page_height(10);
+        assert!(help.is_scrollable());
+
+        help.scroll_down(10);
+        assert_eq!(help.scroll(), 10);
+
+        help.scroll_down(10);
+        assert_eq!(help.scroll(), 15);
+
+        help.scroll_up(1);
+        assert_eq!(help.scroll(), 14);
+
+        help.scroll_up(20);
+        assert_eq!(help.scroll(), 0);
+    }
+
+    #[test]
+    fn it_does_not_scroll_when_everything_fits() {
+        let mut help = help_with_entries(5);
+        help.set_page_height(10);
+        assert!(!help.is_scrollable());
+
+        help.scroll_down(1);
+        assert_eq!(help.scroll(), 0);
+    }
+
+    #[test]
+    fn it_keeps_the_last_page_full_when_the_overlay_grows() {
+        let mut help = help_with_entries(25);
+        help.set_page_height(10);
+        help.scroll_down(15);
+
+        help.set_page_height(20);
+        assert_eq!(help.scroll(), 5);
+    }
+}
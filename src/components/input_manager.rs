@@ -1,7 +1,9 @@
+use std::time::{Duration, Instant};
 use termion::event::Key;
 
-use components::App;
-use models::Mode;
+use components::keymap::{describe_key, Action, Keymap, Lookup};
+use components::{App, Command, Help};
+use models::{Channel, Mode};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Outcome {
@@ -9,71 +11,470 @@ pub enum Outcome {
     Quit,
 }
 
+/// How long a partially typed chord or count is kept around before being discarded.
+const PENDING_TIMEOUT_MS: u64 = 1500;
+
 #[derive(Debug)]
-pub struct KeyManager {}
+pub struct KeyManager {
+    keymap: Keymap,
+    pending: PendingInput,
+}
+
+/// Keys typed so far for a chord, together with any count prefix before it.
+#[derive(Debug, Default)]
+pub struct PendingInput {
+    count: Option<usize>,
+    keys: Vec<Key>,
+    last_input_at: Option<Instant>,
+}
 
 impl KeyManager {
-    pub fn new() -> Self {
-        KeyManager {}
+    pub fn new(keymap: Keymap) -> Self {
+        KeyManager {
+            keymap,
+            pending: PendingInput::default(),
+        }
     }
 
     pub fn handle_key(&mut self, app: &mut App, input: Key) -> Outcome {
-        match app.state().current_mode() {
-            &Mode::History => self.handle_history_key(app, input),
-            &Mode::SelectChannel => self.handle_select_channel_key(app, input),
-        }
-    }
-
-    fn handle_history_key(&mut self, app: &mut App, input: Key) -> Outcome {
-        match input {
-            Key::Char('q') => return Outcome::Quit,
-            Key::Char('j') => app.state_mut().scroll_down(1),
-            Key::Char('k') => app.state_mut().scroll_up(1),
-            Key::Ctrl('f') => {
-                // Leave 2 lines from last page visible
-                let page_size = app.state().chat_height().saturating_sub(2);
-                app.state_mut().scroll_down(page_size as usize);
-            }
-            Key::Ctrl('b') => {
-                // Leave 2 lines from last page visible
-                let page_size = app.state().chat_height().saturating_sub(2);
-                app.state_mut().scroll_up(page_size as usize)
-            }
-            Key::Char('G') => {
-                let distance = app.state().current_history_scroll();
-                app.state_mut().scroll_down(distance)
-            }
-            Key::Char('b') => app.state_mut().add_fake_message(None),
-            Key::Char('B') => app.state_mut().toggle_loading_state(),
-            Key::Ctrl('k') => app.state_mut().enter_mode(Mode::SelectChannel),
-            _ => {}
-        }
-        Outcome::Continue
-    }
-
-    fn handle_select_channel_key(&mut self, app: &mut App, input: Key) -> Outcome {
-        match input {
-            Key::Backspace => app.channel_selector.delete_character(),
-            Key::Ctrl('w') => app.channel_selector.delete_word(),
-            Key::Ctrl('a') => app.channel_selector.move_to_beginning(),
-            Key::Ctrl('e') => app.channel_selector.move_to_end(),
-            Key::Ctrl('k') => app.channel_selector.reset(),
-            Key::Left => app.channel_selector.move_cursor_left(),
-            Key::Right => app.channel_selector.move_cursor_right(),
-            Key::Up => app.channel_selector.select_previous_match(),
-            Key::Down => app.channel_selector.select_next_match(),
-            Key::Char('\n') => {
-                app.select_channel_from_selector();
-                app.channel_selector.reset();
-                app.state_mut().enter_mode(Mode::History);
-            }
-            Key::Esc => {
-                app.channel_selector.reset();
-                app.state_mut().enter_mode(Mode::History);
-            }
-            Key::Char(chr) => app.channel_selector.add_character(chr),
-            _ => {}
-        }
-        Outcome::Continue
+        // The help overlay can be scrolled; any other key closes it.
+        if let Some(mut help) = app.help.take() {
+            let page_height = help.page_height().max(1);
+            match input {
+                Key::Char('j') | Key::Down | Key::Ctrl('n') => help.scroll_down(1),
+                Key::Char('k') | Key::Up | Key::Ctrl('p') => help.scroll_up(1),
+                Key::Char(' ') | Key::PageDown | Key::Ctrl('f') => help.scroll_down(page_height),
+                Key::Char('b') | Key::PageUp | Key::Ctrl('b') => help.scroll_up(page_height),
+                _ => return Outcome::Continue,
+            }
+            app.help = Some(help);
+            return Outcome::Continue;
+        }
+
+        // Deleting a message waits for an answer; anything but "y" keeps the message.
+        if app.pending_deletion.is_some() {
+            if let Err(error) = app.confirm_deletion(input == Key::Char('y')) {
+                app.state_mut()
+                    .add_error_message(error.context("Could not delete message"));
+            }
+            return Outcome::Continue;
+        }
+
+        let mode = *app.state().current_mode();
+        let outcome = match mode {
+            Mode::History | Mode::Normal => self.handle_chord_key(app, mode, input),
+            Mode::Insert | Mode::SelectChannel | Mode::Command => {
+                self.handle_text_key(app, mode, input)
+            }
+        };
+        app.pending_keys = self.pending.describe();
+        outcome
+    }
+
+    /// Discards a partially typed chord or count if the user has not continued it in a while.
+    pub fn tick(&mut self, app: &mut App) {
+        if self.pending.is_expired(Instant::now()) {
+            self.pending.clear();
+            app.pending_keys.clear();
+        }
+    }
+
+    fn handle_chord_key(&mut self, app: &mut App, mode: Mode, input: Key) -> Outcome {
+        // Esc cancels a partial chord before it is used for anything else.
+        if input == Key::Esc && !self.pending.is_empty() {
+            self.pending.clear();
+            return Outcome::Continue;
+        }
+
+        let now = Instant::now();
+        if let Key::Char(chr) = input {
+            if self.pending.push_count_digit(chr, now) {
+                return Outcome::Continue;
+            }
+        }
+        self.pending.push_key(input, now);
+
+        match self.keymap.lookup(mode, self.pending.keys()) {
+            Lookup::Prefix => Outcome::Continue,
+            Lookup::Action(action) => {
+                let count = self.pending.count();
+                self.pending.clear();
+                perform_repeatedly(app, &self.keymap, action, count)
+            }
+            Lookup::Unbound => {
+                self.pending.clear();
+                Outcome::Continue
+            }
+        }
+    }
+
+    /// Handles keys in modes where the user is typing text. Keys without bindings are inserted
+    /// as text.
+    fn handle_text_key(&mut self, app: &mut App, mode: Mode, input: Key) -> Outcome {
+        let action = match self.keymap.lookup(mode, &[input]) {
+            Lookup::Action(action) => Some(action),
+            Lookup::Prefix | Lookup::Unbound => None,
+        };
+
+        // Any other action than completing again accepts the current completion.
+        if action != Some(Action::Complete) {
+            app.completion = None;
+        }
+
+        match (action, input) {
+            (Some(action), _) => perform(app, &self.keymap, action),
+            (None, Key::Char(chr)) => {
+                match mode {
+                    Mode::SelectChannel => app.channel_selector.add_character(chr),
+                    Mode::Command => app.command_line.add_character(chr),
+                    _ => app.composer.add_character(chr),
+                }
+                Outcome::Continue
+            }
+            (None, _) => Outcome::Continue,
+        }
+    }
+}
+
+/// Performs an action `count` times, stopping early if it switches to another mode.
+fn perform_repeatedly(app: &mut App, keymap: &Keymap, action: Action, count: usize) -> Outcome {
+    let mode = *app.state().current_mode();
+    for _ in 0..count {
+        if perform(app, keymap, action) == Outcome::Quit {
+            return Outcome::Quit;
+        }
+        if app.state().current_mode() != &mode {
+            break;
+        }
+    }
+    Outcome::Continue
+}
+
+fn perform(app: &mut App, keymap: &Keymap, action: Action) -> Outcome {
+    let mode = *app.state().current_mode();
+
+    match action {
+        Action::Quit => return Outcome::Quit,
+        Action::ScrollDown => app.state_mut().scroll_down(1),
+        Action::ScrollUp => app.state_mut().scroll_up(1),
+        Action::PageDown => {
+            // Leave 2 lines from last page visible
+            let page_size = app.state().chat_height().saturating_sub(2);
+            app.state_mut().scroll_down(page_size as usize);
+        }
+        Action::PageUp => {
+            // Leave 2 lines from last page visible
+            let page_size = app.state().chat_height().saturating_sub(2);
+            app.state_mut().scroll_up(page_size as usize)
+        }
+        Action::ScrollToTop => {
+            let distance = app.state().max_history_scroll();
+            app.state_mut().scroll_up(distance)
+        }
+        Action::ScrollToBottom => {
+            let distance = app.state().current_history_scroll();
+            app.state_mut().scroll_down(distance)
+        }
+        Action::OpenChannelSelector => app.state_mut().enter_mode(Mode::SelectChannel),
+        Action::AddFakeMessage => app.state_mut().add_fake_message(None),
+        Action::ToggleLoading => app.state_mut().toggle_loading_state(),
+        Action::EnterHistoryMode => {
+            match mode {
+                Mode::SelectChannel => app.channel_selector.reset(),
+                Mode::Command => app.command_line.reset(),
+                _ => {}
+            }
+            app.state_mut().enter_mode(Mode::History);
+        }
+        Action::EnterCommandMode => {
+            app.command_line.reset();
+            app.state_mut().enter_mode(Mode::Command);
+        }
+        Action::RunCommand => {
+            let text = app.command_line.take_text();
+            app.state_mut().enter_mode(Mode::History);
+            match Command::parse(&text).and_then(|command| app.run_command(command)) {
+                Ok(outcome) => return outcome,
+                Err(error) => app
+                    .state_mut()
+                    .add_error_message(error.context("Could not run command")),
+            }
+        }
+        Action::EnterNormalMode => {
+            if mode == Mode::Insert {
+                app.composer.end_insert();
+            }
+            app.state_mut().enter_mode(Mode::Normal);
+        }
+        Action::Insert => {
+            app.composer.begin_insert();
+            app.state_mut().enter_mode(Mode::Insert);
+        }
+        Action::Append => {
+            app.composer.begin_insert();
+            app.composer.move_cursor_right();
+            app.state_mut().enter_mode(Mode::Insert);
+        }
+        Action::InsertAtLineBeginning => {
+            app.composer.begin_insert();
+            app.composer.move_to_beginning();
+            app.state_mut().enter_mode(Mode::Insert);
+        }
+        Action::AppendAtLineEnd => {
+            app.composer.begin_insert();
+            app.composer.move_to_end();
+            app.state_mut().enter_mode(Mode::Insert);
+        }
+        Action::OpenLineBelow => {
+            app.composer.open_line_below();
+            app.state_mut().enter_mode(Mode::Insert);
+        }
+        Action::OpenLineAbove => {
+            app.composer.open_line_above();
+            app.state_mut().enter_mode(Mode::Insert);
+        }
+        Action::ChangeWord => {
+            app.composer.change_word();
+            app.state_mut().enter_mode(Mode::Insert);
+        }
+        Action::MoveLeft => match mode {
+            Mode::Normal => app.composer.move_left_in_line(),
+            Mode::SelectChannel => app.channel_selector.move_cursor_left(),
+            Mode::Command => app.command_line.move_cursor_left(),
+            _ => app.composer.move_cursor_left(),
+        },
+        Action::MoveRight => match mode {
+            Mode::Normal => app.composer.move_right_in_line(),
+            Mode::SelectChannel => app.channel_selector.move_cursor_right(),
+            Mode::Command => app.command_line.move_cursor_right(),
+            _ => app.composer.move_cursor_right(),
+        },
+        Action::MoveUp => match mode {
+            Mode::Normal => app.composer.move_up_in_normal_mode(),
+            Mode::Insert if app.composer.text().is_empty() && app.editing.is_none() => {
+                app.edit_last_message()
+            }
+            _ => app.composer.move_cursor_up(),
+        },
+        Action::MoveDown => match mode {
+            Mode::Normal => app.composer.move_down_in_normal_mode(),
+            _ => app.composer.move_cursor_down(),
+        },
+        Action::WordForward => app.composer.move_word_forward(),
+        Action::WordBackward => app.composer.move_word_backward(),
+        Action::LineBeginning => match mode {
+            Mode::SelectChannel => app.channel_selector.move_to_beginning(),
+            Mode::Command => app.command_line.move_to_beginning(),
+            _ => app.composer.move_to_beginning(),
+        },
+        Action::LineEnd => match mode {
+            Mode::Normal => app.composer.move_to_last_character(),
+            Mode::SelectChannel => app.channel_selector.move_to_end(),
+            Mode::Command => app.command_line.move_to_end(),
+            _ => app.composer.move_to_end(),
+        },
+        Action::FirstLine => app.composer.move_to_first_line(),
+        Action::LastLine => app.composer.move_to_last_line(),
+        Action::DeleteCharacter => app.composer.delete_character_under_cursor(),
+        Action::DeleteCharacterBackward => match mode {
+            Mode::SelectChannel => app.channel_selector.delete_character(),
+            Mode::Command => app.command_line.delete_character(),
+            _ => app.composer.delete_character(),
+        },
+        Action::DeleteWordBackward => match mode {
+            Mode::SelectChannel => app.channel_selector.delete_word(),
+            Mode::Command => app.command_line.delete_word(),
+            _ => app.composer.delete_word(),
+        },
+        Action::DeleteWord => app.composer.delete_word_forward(),
+        Action::DeleteLine => app.composer.delete_line(),
+        Action::Undo => app.composer.undo(),
+        Action::Redo => app.composer.redo(),
+        Action::Complete => match mode {
+            Mode::Command => app.complete_command(),
+            _ => app.complete_word(),
+        },
+        Action::SendMessage => {
+            if let Err(error) = app.send_composed_message() {
+                app.state_mut()
+                    .add_error_message(error.context("Could not send message"));
+            }
+            app.state_mut().enter_mode(Mode::History);
+        }
+        Action::SelectNextMatch => app.channel_selector.select_next_match(),
+        Action::SelectPreviousMatch => app.channel_selector.select_previous_match(),
+        Action::SelectChannel => {
+            if let Err(error) = app.select_channel_from_selector() {
+                app.state_mut()
+                    .add_error_message(error.context("Could not select channel"));
+            }
+            app.channel_selector.reset();
+            app.state_mut().enter_mode(Mode::History);
+        }
+        Action::ClearSelector => app.channel_selector.reset(),
+        Action::ToggleStar => {
+            let context = match app.state().selected_channel().map(Channel::is_starred) {
+                Some(true) => "Could not unstar channel",
+                _ => "Could not star channel",
+            };
+            if let Err(error) = app.toggle_star_selected_channel() {
+                app.state_mut().add_error_message(error.context(context));
+            }
+        }
+        Action::SelectPreviousMessage => app.state_mut().select_previous_message(),
+        Action::SelectNextMessage => app.state_mut().select_next_message(),
+        Action::ClearMessageSelection => app.state_mut().select_message(None),
+        Action::React => {
+            // Pick the emoji with the completion of the :react command.
+            app.command_line.reset();
+            app.command_line.replace_before_cursor(0, "react ");
+            app.state_mut().enter_mode(Mode::Command);
+        }
+        Action::OpenThread => {
+            if let Err(error) = app.open_thread() {
+                app.state_mut()
+                    .add_error_message(error.context("Could not open thread"));
+            }
+        }
+        Action::CloseThread => app.close_thread(),
+        Action::EditMessage => match app.edit_selected_message() {
+            Ok(_) => {
+                app.composer.begin_insert();
+                app.state_mut().enter_mode(Mode::Insert);
+            }
+            Err(error) => app
+                .state_mut()
+                .add_error_message(error.context("Could not edit message")),
+        },
+        Action::CancelEdit => app.cancel_editing(),
+        Action::DeleteMessage => {
+            if let Err(error) = app.delete_selected_message() {
+                app.state_mut()
+                    .add_error_message(error.context("Could not delete message"));
+            }
+        }
+        Action::ToggleBroadcast => app.broadcast_reply = !app.broadcast_reply,
+        Action::ShowHelp => app.help = Some(Help::new(keymap.help(mode))),
+    }
+    Outcome::Continue
+}
+
+impl PendingInput {
+    /// Adds a digit to the count prefix, if the character can be part of one.
+    ///
+    /// A `0` can only continue a count, as it is a motion of its own otherwise.
+    pub fn push_count_digit(&mut self, chr: char, now: Instant) -> bool {
+        if !self.keys.is_empty() {
+            return false;
+        }
+
+        let digit = match chr.to_digit(10) {
+            Some(0) if self.count.is_none() => return false,
+            Some(digit) => digit as usize,
+            None => return false,
+        };
+
+        self.count = Some(
+            self.count
+                .unwrap_or(0)
+                .saturating_mul(10)
+                .saturating_add(digit),
+        );
+        self.last_input_at = Some(now);
+        true
+    }
+
+    pub fn push_key(&mut self, key: Key, now: Instant) {
+        self.keys.push(key);
+        self.last_input_at = Some(now);
+    }
+
+    pub fn keys(&self) -> &[Key] {
+        &self.keys
+    }
+
+    /// Returns the count prefix, defaulting to 1.
+    pub fn count(&self) -> usize {
+        self.count.unwrap_or(1)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count.is_none() && self.keys.is_empty()
+    }
+
+    pub fn is_expired(&self, now: Instant) -> bool {
+        match self.last_input_at {
+            Some(time) => now.duration_since(time) >= Duration::from_millis(PENDING_TIMEOUT_MS),
+            None => false,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.count = None;
+        self.keys.clear();
+        self.last_input_at = None;
+    }
+
+    /// Describes the pending input for display, like `5g`.
+    pub fn describe(&self) -> String {
+        let mut description = self.count.map(|c| c.to_string()).unwrap_or_default();
+        for key in &self.keys {
+            description.push_str(&describe_key(*key));
+        }
+        description
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_collects_count_prefixes() {
+        let now = Instant::now();
+        let mut pending = PendingInput::default();
+        assert_eq!(pending.count(), 1);
+
+        assert!(pending.push_count_digit('1', now));
+        assert!(pending.push_count_digit('0', now));
+        assert!(!pending.push_count_digit('x', now));
+        assert_eq!(pending.count(), 10);
+
+        pending.push_key(Key::Char('j'), now);
+        assert!(!pending.push_count_digit('2', now));
+        assert_eq!(pending.keys(), &[Key::Char('j')]);
+    }
+
+    #[test]
+    fn it_does_not_start_counts_with_zero() {
+        let mut pending = PendingInput::default();
+        assert!(!pending.push_count_digit('0', Instant::now()));
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn it_describes_pending_input() {
+        let now = Instant::now();
+        let mut pending = PendingInput::default();
+        assert_eq!(pending.describe(), "");
+
+        pending.push_count_digit('3', now);
+        pending.push_key(Key::Char('g'), now);
+        assert_eq!(pending.describe(), "3g");
+
+        pending.clear();
+        pending.push_key(Key::Ctrl('f'), now);
+        assert_eq!(pending.describe(), "<C-f>");
+    }
+
+    #[test]
+    fn it_expires_after_a_timeout() {
+        let now = Instant::now();
+        let mut pending = PendingInput::default();
+        assert!(!pending.is_expired(now));
+
+        pending.push_key(Key::Char('g'), now);
+        assert!(!pending.is_expired(now + Duration::from_millis(PENDING_TIMEOUT_MS - 1)));
+        assert!(pending.is_expired(now + Duration::from_millis(PENDING_TIMEOUT_MS)));
     }
 }
@@ -0,0 +1,761 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use failure::{Error, Fail, ResultExt};
+use termion::event::Key;
+
+use models::Mode;
+
+/// Something the user can do by pressing keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    ScrollDown,
+    ScrollUp,
+    PageDown,
+    PageUp,
+    ScrollToTop,
+    ScrollToBottom,
+    OpenChannelSelector,
+    AddFakeMessage,
+    ToggleLoading,
+    EnterHistoryMode,
+    EnterNormalMode,
+    Insert,
+    Append,
+    InsertAtLineBeginning,
+    AppendAtLineEnd,
+    OpenLineBelow,
+    OpenLineAbove,
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    WordForward,
+    WordBackward,
+    LineBeginning,
+    LineEnd,
+    FirstLine,
+    LastLine,
+    DeleteCharacter,
+    DeleteCharacterBackward,
+    DeleteWordBackward,
+    DeleteWord,
+    DeleteLine,
+    ChangeWord,
+    Undo,
+    Redo,
+    Complete,
+    SendMessage,
+    SelectNextMatch,
+    SelectPreviousMatch,
+    SelectChannel,
+    ClearSelector,
+    EnterCommandMode,
+    RunCommand,
+    ToggleStar,
+    SelectPreviousMessage,
+    SelectNextMessage,
+    ClearMessageSelection,
+    React,
+    OpenThread,
+    CloseThread,
+    ToggleBroadcast,
+    EditMessage,
+    CancelEdit,
+    DeleteMessage,
+    ShowHelp,
+}
+
+/// Names of actions as used in the config file, and descriptions for the help overlay.
+const ACTIONS: &[(&str, Action, &str)] = &[
+    ("quit", Action::Quit, "Quit"),
+    ("scroll_down", Action::ScrollDown, "Scroll down"),
+    ("scroll_up", Action::ScrollUp, "Scroll up"),
+    ("page_down", Action::PageDown, "Scroll down one page"),
+    ("page_up", Action::PageUp, "Scroll up one page"),
+    ("scroll_to_top", Action::ScrollToTop, "Scroll to the top"),
+    ("scroll_to_bottom", Action::ScrollToBottom, "Scroll to the bottom"),
+    ("open_channel_selector", Action::OpenChannelSelector, "Switch channel"),
+    ("add_fake_message", Action::AddFakeMessage, "Add a fake message (debug)"),
+    ("toggle_loading", Action::ToggleLoading, "Toggle loading indicator (debug)"),
+    ("enter_history_mode", Action::EnterHistoryMode, "Go back to History mode"),
+    ("enter_normal_mode", Action::EnterNormalMode, "Go to Normal mode"),
+    ("insert", Action::Insert, "Insert before cursor"),
+    ("append", Action::Append, "Append after cursor"),
+    ("insert_at_line_beginning", Action::InsertAtLineBeginning, "Insert at beginning of line"),
+    ("append_at_line_end", Action::AppendAtLineEnd, "Append at end of line"),
+    ("open_line_below", Action::OpenLineBelow, "Open new line below"),
+    ("open_line_above", Action::OpenLineAbove, "Open new line above"),
+    ("move_left", Action::MoveLeft, "Move cursor left"),
+    ("move_right", Action::MoveRight, "Move cursor right"),
+    ("move_up", Action::MoveUp, "Move cursor up"),
+    ("move_down", Action::MoveDown, "Move cursor down"),
+    ("word_forward", Action::WordForward, "Next word"),
+    ("word_backward", Action::WordBackward, "Previous word"),
+    ("line_beginning", Action::LineBeginning, "Go to beginning of line"),
+    ("line_end", Action::LineEnd, "Go to end of line"),
+    ("first_line", Action::FirstLine, "Go to first line"),
+    ("last_line", Action::LastLine, "Go to last line"),
+    ("delete_character", Action::DeleteCharacter, "Delete character under cursor"),
+    (
+        "delete_character_backward",
+        Action::DeleteCharacterBackward,
+        "Delete character before cursor",
+    ),
+    ("delete_word_backward", Action::DeleteWordBackward, "Delete word before cursor"),
+    ("delete_word", Action::DeleteWord, "Delete word"),
+    ("delete_line", Action::DeleteLine, "Delete line"),
+    ("change_word", Action::ChangeWord, "Change word"),
+    ("undo", Action::Undo, "Undo"),
+    ("redo", Action::Redo, "Redo"),
+    ("complete", Action::Complete, "Complete name"),
+    ("send_message", Action::SendMessage, "Send message"),
+    ("select_next_match", Action::SelectNextMatch, "Select next channel"),
+    ("select_previous_match", Action::SelectPreviousMatch, "Select previous channel"),
+    ("select_channel", Action::SelectChannel, "Open selected channel"),
+    ("clear_selector", Action::ClearSelector, "Clear search"),
+    ("enter_command_mode", Action::EnterCommandMode, "Enter a command"),
+    ("run_command", Action::RunCommand, "Run command"),
+    ("toggle_star", Action::ToggleStar, "Star or unstar the channel"),
+    ("select_previous_message", Action::SelectPreviousMessage, "Select previous message"),
+    ("select_next_message", Action::SelectNextMessage, "Select next message"),
+    ("clear_message_selection", Action::ClearMessageSelection, "Clear message selection"),
+    ("react", Action::React, "React to the selected message"),
+    ("open_thread", Action::OpenThread, "Open thread of the selected message"),
+    ("close_thread", Action::CloseThread, "Close thread"),
+    ("toggle_broadcast", Action::ToggleBroadcast, "Also send thread replies to the channel"),
+    ("edit_message", Action::EditMessage, "Edit the selected message"),
+    ("cancel_edit", Action::CancelEdit, "Stop editing the message"),
+    ("delete_message", Action::DeleteMessage, "Delete the selected message"),
+    ("show_help", Action::ShowHelp, "Show key bindings"),
+];
+
+/// Names of modes, as used for sections in the config file.
+const MODE_NAMES: &[(&str, Mode)] = &[
+    ("history", Mode::History),
+    ("normal", Mode::Normal),
+    ("insert", Mode::Insert),
+    ("select_channel", Mode::SelectChannel),
+    ("command", Mode::Command),
+];
+
+const DEFAULT_BINDINGS: &[(Mode, &str, Action)] = &[
+    (Mode::History, "q", Action::Quit),
+    (Mode::History, "j", Action::ScrollDown),
+    (Mode::History, "k", Action::ScrollUp),
+    (Mode::History, "<C-f>", Action::PageDown),
+    (Mode::History, "<C-b>", Action::PageUp),
+    (Mode::History, "G", Action::ScrollToBottom),
+    (Mode::History, "gg", Action::ScrollToTop),
+    (Mode::History, "gn", Action::EnterNormalMode),
+    (Mode::History, "b", Action::AddFakeMessage),
+    (Mode::History, "B", Action::ToggleLoading),
+    (Mode::History, "<C-k>", Action::OpenChannelSelector),
+    (Mode::History, "i", Action::Insert),
+    (Mode::History, ":", Action::EnterCommandMode),
+    (Mode::History, "s", Action::ToggleStar),
+    (Mode::History, "K", Action::SelectPreviousMessage),
+    (Mode::History, "<Up>", Action::SelectPreviousMessage),
+    (Mode::History, "J", Action::SelectNextMessage),
+    (Mode::History, "<Down>", Action::SelectNextMessage),
+    (Mode::History, "<Esc>", Action::ClearMessageSelection),
+    (Mode::History, "r", Action::React),
+    (Mode::History, "t", Action::OpenThread),
+    (Mode::History, "T", Action::CloseThread),
+    (Mode::History, "e", Action::EditMessage),
+    (Mode::History, "dd", Action::DeleteMessage),
+    (Mode::History, "?", Action::ShowHelp),
+    (Mode::History, "<F1>", Action::ShowHelp),
+    (Mode::Normal, "h", Action::MoveLeft),
+    (Mode::Normal, "<Left>", Action::MoveLeft),
+    (Mode::Normal, "l", Action::MoveRight),
+    (Mode::Normal, "<Right>", Action::MoveRight),
+    (Mode::Normal, "k", Action::MoveUp),
+    (Mode::Normal, "<Up>", Action::MoveUp),
+    (Mode::Normal, "j", Action::MoveDown),
+    (Mode::Normal, "<Down>", Action::MoveDown),
+    (Mode::Normal, "w", Action::WordForward),
+    (Mode::Normal, "b", Action::WordBackward),
+    (Mode::Normal, "0", Action::LineBeginning),
+    (Mode::Normal, "$", Action::LineEnd),
+    (Mode::Normal, "gg", Action::FirstLine),
+    (Mode::Normal, "G", Action::LastLine),
+    (Mode::Normal, "<C-g>", Action::LastLine),
+    (Mode::Normal, "x", Action::DeleteCharacter),
+    (Mode::Normal, "dd", Action::DeleteLine),
+    (Mode::Normal, "dw", Action::DeleteWord),
+    (Mode::Normal, "cw", Action::ChangeWord),
+    (Mode::Normal, "u", Action::Undo),
+    (Mode::Normal, "<C-r>", Action::Redo),
+    (Mode::Normal, "i", Action::Insert),
+    (Mode::Normal, "a", Action::Append),
+    (Mode::Normal, "I", Action::InsertAtLineBeginning),
+    (Mode::Normal, "A", Action::AppendAtLineEnd),
+    (Mode::Normal, "o", Action::OpenLineBelow),
+    (Mode::Normal, "O", Action::OpenLineAbove),
+    (Mode::Normal, "<C-d>", Action::SendMessage),
+    (Mode::Normal, "<C-t>", Action::ToggleBroadcast),
+    (Mode::Normal, "<C-c>", Action::CancelEdit),
+    (Mode::Normal, "gh", Action::EnterHistoryMode),
+    (Mode::Normal, "<Esc>", Action::EnterHistoryMode),
+    (Mode::Normal, ":", Action::EnterCommandMode),
+    (Mode::Normal, "?", Action::ShowHelp),
+    (Mode::Normal, "<F1>", Action::ShowHelp),
+    (Mode::Insert, "<Tab>", Action::Complete),
+    (Mode::Insert, "<BS>", Action::DeleteCharacterBackward),
+    (Mode::Insert, "<C-w>", Action::DeleteWordBackward),
+    (Mode::Insert, "<C-a>", Action::LineBeginning),
+    (Mode::Insert, "<C-e>", Action::LineEnd),
+    (Mode::Insert, "<Left>", Action::MoveLeft),
+    (Mode::Insert, "<Right>", Action::MoveRight),
+    (Mode::Insert, "<Up>", Action::MoveUp),
+    (Mode::Insert, "<Down>", Action::MoveDown),
+    (Mode::Insert, "<C-d>", Action::SendMessage),
+    (Mode::Insert, "<C-t>", Action::ToggleBroadcast),
+    (Mode::Insert, "<C-c>", Action::CancelEdit),
+    (Mode::Insert, "<Esc>", Action::EnterNormalMode),
+    (Mode::Insert, "<F1>", Action::ShowHelp),
+    (Mode::SelectChannel, "<BS>", Action::DeleteCharacterBackward),
+    (Mode::SelectChannel, "<C-w>", Action::DeleteWordBackward),
+    (Mode::SelectChannel, "<C-a>", Action::LineBeginning),
+    (Mode::SelectChannel, "<C-e>", Action::LineEnd),
+    (Mode::SelectChannel, "<C-k>", Action::ClearSelector),
+    (Mode::SelectChannel, "<Left>", Action::MoveLeft),
+    (Mode::SelectChannel, "<Right>", Action::MoveRight),
+    (Mode::SelectChannel, "<Up>", Action::SelectPreviousMatch),
+    (Mode::SelectChannel, "<Down>", Action::SelectNextMatch),
+    (Mode::SelectChannel, "<Enter>", Action::SelectChannel),
+    (Mode::SelectChannel, "<Esc>", Action::EnterHistoryMode),
+    (Mode::SelectChannel, "<F1>", Action::ShowHelp),
+    (Mode::Command, "<Tab>", Action::Complete),
+    (Mode::Command, "<BS>", Action::DeleteCharacterBackward),
+    (Mode::Command, "<C-w>", Action::DeleteWordBackward),
+    (Mode::Command, "<C-a>", Action::LineBeginning),
+    (Mode::Command, "<C-e>", Action::LineEnd),
+    (Mode::Command, "<Left>", Action::MoveLeft),
+    (Mode::Command, "<Right>", Action::MoveRight),
+    (Mode::Command, "<Enter>", Action::RunCommand),
+    (Mode::Command, "<Esc>", Action::EnterHistoryMode),
+    (Mode::Command, "<F1>", Action::ShowHelp),
+];
+
+/// Named keys in the `<...>` notation, apart from modifier combinations.
+const KEY_NAMES: &[(&str, Key)] = &[
+    ("Enter", Key::Char('\n')),
+    ("Tab", Key::Char('\t')),
+    ("Space", Key::Char(' ')),
+    ("lt", Key::Char('<')),
+    ("BS", Key::Backspace),
+    ("Left", Key::Left),
+    ("Right", Key::Right),
+    ("Up", Key::Up),
+    ("Down", Key::Down),
+    ("Home", Key::Home),
+    ("End", Key::End),
+    ("PageUp", Key::PageUp),
+    ("PageDown", Key::PageDown),
+    ("Del", Key::Delete),
+    ("Insert", Key::Insert),
+    ("Esc", Key::Esc),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Binding {
+    mode: Mode,
+    keys: Vec<Key>,
+    action: Action,
+}
+
+/// Result of looking up a key sequence in a `Keymap`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lookup {
+    Action(Action),
+    /// The keys are the start of one or more longer bindings.
+    Prefix,
+    Unbound,
+}
+
+/// Key bindings for every mode.
+///
+/// The defaults can be overridden by `slack-tui/keys.ini` in the XDG config directory:
+///
+/// ```ini
+/// [history]
+/// <C-n> = scroll_down
+/// gg = none
+/// ```
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: Vec<Binding>,
+}
+
+impl Action {
+    pub fn from_name(name: &str) -> Option<Action> {
+        ACTIONS
+            .iter()
+            .find(|&&(action_name, _, _)| action_name == name)
+            .map(|&(_, action, _)| action)
+    }
+
+    pub fn description(self) -> &'static str {
+        ACTIONS
+            .iter()
+            .find(|&&(_, action, _)| action == self)
+            .map(|&(_, _, description)| description)
+            .expect("All actions have descriptions")
+    }
+
+    pub fn is_available_in(self, mode: Mode) -> bool {
+        use self::Action::*;
+        if self == ShowHelp {
+            return true;
+        }
+
+        match mode {
+            Mode::History => match self {
+                Quit | ScrollDown | ScrollUp | PageDown | PageUp | ScrollToTop | ScrollToBottom
+                | OpenChannelSelector | AddFakeMessage | ToggleLoading | EnterNormalMode
+                | Insert | EnterCommandMode | ToggleStar | SelectPreviousMessage | SelectNextMessage
+                | ClearMessageSelection | React | OpenThread | CloseThread | EditMessage
+                | DeleteMessage => true,
+                _ => false,
+            },
+            Mode::Normal => match self {
+                MoveLeft | MoveRight | MoveUp | MoveDown | WordForward | WordBackward
+                | LineBeginning | LineEnd | FirstLine | LastLine | DeleteCharacter | DeleteWord
+                | DeleteLine | ChangeWord | Undo | Redo | Insert | Append
+                | InsertAtLineBeginning | AppendAtLineEnd | OpenLineBelow | OpenLineAbove
+                | SendMessage | ToggleBroadcast | CancelEdit | EnterHistoryMode
+                | EnterCommandMode => true,
+                _ => false,
+            },
+            Mode::Insert => match self {
+                Complete | DeleteCharacterBackward | DeleteWordBackward | LineBeginning
+                | LineEnd | MoveLeft | MoveRight | MoveUp | MoveDown | SendMessage
+                | ToggleBroadcast | CancelEdit | EnterNormalMode => true,
+                _ => false,
+            },
+            Mode::SelectChannel => match self {
+                DeleteCharacterBackward | DeleteWordBackward | LineBeginning | LineEnd
+                | ClearSelector | MoveLeft | MoveRight | SelectPreviousMatch | SelectNextMatch
+                | SelectChannel | EnterHistoryMode => true,
+                _ => false,
+            },
+            Mode::Command => match self {
+                Complete | DeleteCharacterBackward | DeleteWordBackward | LineBeginning
+                | LineEnd | MoveLeft | MoveRight | RunCommand | EnterHistoryMode => true,
+                _ => false,
+            },
+        }
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap {
+            bindings: DEFAULT_BINDINGS
+                .iter()
+                .map(|&(mode, keys, action)| Binding {
+                    mode,
+                    keys: parse_keys(keys).expect("Default bindings are valid"),
+                    action,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Keymap {
+    /// Loads the default bindings, overridden by the user's config file if there is one.
+    pub fn load() -> Result<Keymap, Error> {
+        let mut keymap = Keymap::default();
+        let path = match config_path() {
+            Some(path) => path,
+            None => return Ok(keymap),
+        };
+
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(ref error) if error.kind() == io::ErrorKind::NotFound => return Ok(keymap),
+            Err(error) => {
+                return Err(error
+                    .context(format!("Could not read {}", path.display()))
+                    .into())
+            }
+        };
+
+        keymap
+            .apply_config(&contents)
+            .with_context(|_| format!("Invalid key bindings in {}", path.display()))?;
+        Ok(keymap)
+    }
+
+    /// Applies bindings from a config file on top of the current ones.
+    ///
+    /// Each section is a mode, and each line binds a key sequence to an action name. Binding to
+    /// `none` removes a binding.
+    pub fn apply_config(&mut self, contents: &str) -> Result<(), Error> {
+        let mut mode = None;
+
+        for (index, line) in contents.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+
+            if line.starts_with('[') && line.ends_with(']') {
+                let name = line[1..line.len() - 1].trim();
+                mode = Some(mode_from_name(name).ok_or_else(|| {
+                    format_err!("Line {}: Unknown mode \"{}\"", line_number, name)
+                })?);
+                continue;
+            }
+
+            let mode = mode.ok_or_else(|| {
+                format_err!("Line {}: Binding outside of a [mode] section", line_number)
+            })?;
+
+            // The key itself might be "=", so look for the separator after the first character.
+            let separator = line
+                .char_indices()
+                .skip(1)
+                .find(|&(_, chr)| chr == '=')
+                .map(|(index, _)| index)
+                .ok_or_else(|| format_err!("Line {}: Expected \"keys = action\"", line_number))?;
+            let keys_text = line[..separator].trim();
+            let action_name = line[separator + 1..].trim();
+
+            let keys = parse_keys(keys_text)
+                .with_context(|_| format!("Line {}: Cannot parse keys", line_number))?;
+
+            if action_name == "none" {
+                self.unbind(mode, &keys);
+                continue;
+            }
+
+            let action = Action::from_name(action_name).ok_or_else(|| {
+                format_err!("Line {}: Unknown action \"{}\"", line_number, action_name)
+            })?;
+            if !action.is_available_in(mode) {
+                return Err(format_err!(
+                    "Line {}: Action \"{}\" is not available in {} mode",
+                    line_number,
+                    action_name,
+                    mode_name(mode)
+                ));
+            }
+            if keys.len() > 1 && mode != Mode::History && mode != Mode::Normal {
+                return Err(format_err!(
+                    "Line {}: Multi-key bindings are not supported in {} mode",
+                    line_number,
+                    mode_name(mode)
+                ));
+            }
+
+            self.bind(mode, keys, action);
+        }
+
+        Ok(())
+    }
+
+    pub fn bind(&mut self, mode: Mode, keys: Vec<Key>, action: Action) {
+        self.unbind(mode, &keys);
+        self.bindings.push(Binding { mode, keys, action });
+    }
+
+    pub fn unbind(&mut self, mode: Mode, keys: &[Key]) {
+        self.bindings
+            .retain(|binding| !(binding.mode == mode && binding.keys.as_slice() == keys));
+    }
+
+    pub fn lookup(&self, mode: Mode, keys: &[Key]) -> Lookup {
+        let mut is_prefix = false;
+        for binding in self.bindings.iter().filter(|binding| binding.mode == mode) {
+            if binding.keys.as_slice() == keys {
+                return Lookup::Action(binding.action);
+            }
+            if binding.keys.starts_with(keys) {
+                is_prefix = true;
+            }
+        }
+
+        if is_prefix {
+            Lookup::Prefix
+        } else {
+            Lookup::Unbound
+        }
+    }
+
+    /// Lists the bindings of a mode for the help overlay, as the keys of each action together
+    /// with its description.
+    pub fn help(&self, mode: Mode) -> Vec<(String, &'static str)> {
+        let mut actions: Vec<(Action, Vec<String>)> = Vec::new();
+
+        for binding in self.bindings.iter().filter(|binding| binding.mode == mode) {
+            let keys: String = binding.keys.iter().map(|key| describe_key(*key)).collect();
+            match actions.iter().position(|&(action, _)| action == binding.action) {
+                Some(index) => actions[index].1.push(keys),
+                None => actions.push((binding.action, vec![keys])),
+            }
+        }
+
+        actions
+            .into_iter()
+            .map(|(action, keys)| (keys.join(", "), action.description()))
+            .collect()
+    }
+}
+
+fn config_path() -> Option<PathBuf> {
+    let config_dir = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_dir.join("slack-tui").join("keys.ini"))
+}
+
+fn mode_from_name(name: &str) -> Option<Mode> {
+    MODE_NAMES
+        .iter()
+        .find(|&&(mode_name, _)| mode_name == name)
+        .map(|&(_, mode)| mode)
+}
+
+pub fn mode_name(mode: Mode) -> &'static str {
+    MODE_NAMES
+        .iter()
+        .find(|&&(_, named_mode)| named_mode == mode)
+        .map(|&(name, _)| name)
+        .expect("All modes have names")
+}
+
+/// Parses a key sequence written in Vim-like notation, like `gg`, `<C-f>` or `<Esc>`.
+pub fn parse_keys(text: &str) -> Result<Vec<Key>, Error> {
+    let mut keys = Vec::new();
+    let mut rest = text;
+
+    while let Some(chr) = rest.chars().next() {
+        if chr == '<' {
+            let end = rest
+                .find('>')
+                .ok_or_else(|| format_err!("Unterminated \"<\" in \"{}\"", text))?;
+            keys.push(parse_key_name(&rest[1..end])?);
+            rest = &rest[end + 1..];
+        } else if chr.is_whitespace() {
+            return Err(format_err!(
+                "Unexpected whitespace in \"{}\"; use <Space> instead",
+                text
+            ));
+        } else {
+            keys.push(Key::Char(chr));
+            rest = &rest[chr.len_utf8()..];
+        }
+    }
+
+    if keys.is_empty() {
+        Err(format_err!("No keys given"))
+    } else {
+        Ok(keys)
+    }
+}
+
+fn parse_key_name(name: &str) -> Result<Key, Error> {
+    if let Some(&(_, key)) = KEY_NAMES.iter().find(|&&(key_name, _)| key_name == name) {
+        return Ok(key);
+    }
+
+    let single_char = |text: &str| {
+        let mut chars = text.chars();
+        match (chars.next(), chars.next()) {
+            (Some(chr), None) => Some(chr),
+            _ => None,
+        }
+    };
+
+    let key = if name.starts_with("C-") {
+        single_char(&name[2..]).map(Key::Ctrl)
+    } else if name.starts_with("M-") {
+        single_char(&name[2..]).map(Key::Alt)
+    } else if name.starts_with('F') {
+        name[1..].parse().ok().map(Key::F)
+    } else {
+        None
+    };
+
+    key.ok_or_else(|| format_err!("Unknown key \"<{}>\"", name))
+}
+
+/// Describes a key in the same notation as `parse_keys` accepts.
+pub fn describe_key(key: Key) -> String {
+    if let Some(&(name, _)) = KEY_NAMES.iter().find(|&&(_, named_key)| named_key == key) {
+        return format!("<{}>", name);
+    }
+
+    match key {
+        Key::Char(chr) => chr.to_string(),
+        Key::Ctrl(chr) => format!("<C-{}>", chr),
+        Key::Alt(chr) => format!("<M-{}>", chr),
+        Key::F(number) => format!("<F{}>", number),
+        _ => String::from("<?>"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_parses_key_notation() {
+        assert_eq!(
+            parse_keys("g<C-f><Esc><lt>$").unwrap(),
+            vec![
+                Key::Char('g'),
+                Key::Ctrl('f'),
+                Key::Esc,
+                Key::Char('<'),
+                Key::Char('$'),
+            ]
+        );
+        assert_eq!(parse_keys("<M-x><F5>").unwrap(), vec![Key::Alt('x'), Key::F(5)]);
+
+        assert!(parse_keys("").is_err());
+        assert!(parse_keys("<C-f").is_err());
+        assert!(parse_keys("<Bogus>").is_err());
+        assert!(parse_keys("<C-ab>").is_err());
+        assert!(parse_keys("g g").is_err());
+    }
+
+    #[test]
+    fn it_describes_keys_in_parsable_notation() {
+        for &key in &[
+            Key::Char('g'),
+            Key::Char('<'),
+            Key::Char('\n'),
+            Key::Ctrl('f'),
+            Key::Backspace,
+            Key::F(12),
+        ] {
+            assert_eq!(parse_keys(&describe_key(key)).unwrap(), vec![key]);
+        }
+    }
+
+    #[test]
+    fn it_names_all_actions() {
+        for &(name, action, _) in ACTIONS {
+            assert_eq!(Action::from_name(name), Some(action));
+            let names = ACTIONS.iter().filter(|&&(_, other, _)| other == action);
+            assert_eq!(names.count(), 1, "{:?} should have exactly one name", action);
+        }
+        assert_eq!(Action::from_name("explode"), None);
+    }
+
+    #[test]
+    fn it_has_valid_default_bindings() {
+        let keymap = Keymap::default();
+        for binding in &keymap.bindings {
+            assert!(
+                binding.action.is_available_in(binding.mode),
+                "{:?} is not available in {:?}",
+                binding.action,
+                binding.mode
+            );
+        }
+    }
+
+    #[test]
+    fn it_looks_up_chords() {
+        let keymap = Keymap::default();
+
+        assert_eq!(
+            keymap.lookup(Mode::History, &[Key::Char('j')]),
+            Lookup::Action(Action::ScrollDown)
+        );
+        assert_eq!(keymap.lookup(Mode::History, &[Key::Char('g')]), Lookup::Prefix);
+        assert_eq!(
+            keymap.lookup(Mode::History, &[Key::Char('g'), Key::Char('g')]),
+            Lookup::Action(Action::ScrollToTop)
+        );
+        assert_eq!(
+            keymap.lookup(Mode::History, &[Key::Char('g'), Key::Char('x')]),
+            Lookup::Unbound
+        );
+        assert_eq!(
+            keymap.lookup(Mode::Normal, &[Key::Char('j')]),
+            Lookup::Action(Action::MoveDown)
+        );
+        assert_eq!(
+            keymap.lookup(Mode::Normal, &[Key::Ctrl('g')]),
+            Lookup::Action(Action::LastLine)
+        );
+    }
+
+    #[test]
+    fn it_lists_bindings_for_help() {
+        let mut keymap = Keymap::default();
+        keymap.bind(Mode::History, vec![Key::Ctrl('n')], Action::ScrollDown);
+        let help = keymap.help(Mode::History);
+
+        assert_eq!(help[0], (String::from("q"), "Quit"));
+        assert!(help.contains(&(String::from("j, <C-n>"), "Scroll down")));
+        assert!(help.contains(&(String::from("gg"), "Scroll to the top")));
+        assert!(help.iter().all(|&(_, description)| description != "Undo"));
+    }
+
+    #[test]
+    fn it_applies_config_overrides() {
+        let mut keymap = Keymap::default();
+        keymap
+            .apply_config(
+                "# Comment\n\
+                 [history]\n\
+                 <C-n> = scroll_down\n\
+                 j = scroll_up\n\
+                 gg = none\n\
+                 \n\
+                 [insert]\n\
+                 = = complete\n",
+            )
+            .unwrap();
+
+        assert_eq!(
+            keymap.lookup(Mode::History, &[Key::Ctrl('n')]),
+            Lookup::Action(Action::ScrollDown)
+        );
+        assert_eq!(
+            keymap.lookup(Mode::History, &[Key::Char('j')]),
+            Lookup::Action(Action::ScrollUp)
+        );
+        assert_eq!(
+            keymap.lookup(Mode::History, &[Key::Char('g'), Key::Char('g')]),
+            Lookup::Unbound
+        );
+        assert_eq!(
+            keymap.lookup(Mode::Insert, &[Key::Char('=')]),
+            Lookup::Action(Action::Complete)
+        );
+    }
+
+    #[test]
+    fn it_reports_config_errors() {
+        let error_for =
+            |config: &str| Keymap::default().apply_config(config).unwrap_err().to_string();
+
+        assert_eq!(error_for("[history]\nj = explode"), "Line 2: Unknown action \"explode\"");
+        assert_eq!(error_for("[history]\n<Bogus> = quit"), "Line 2: Cannot parse keys");
+        assert_eq!(error_for("[visual]"), "Line 1: Unknown mode \"visual\"");
+        assert_eq!(error_for("j = quit"), "Line 1: Binding outside of a [mode] section");
+        assert_eq!(error_for("[history]\nj quit"), "Line 2: Expected \"keys = action\"");
+        assert_eq!(
+            error_for("[insert]\nj = quit"),
+            "Line 2: Action \"quit\" is not available in insert mode"
+        );
+        assert_eq!(
+            error_for("[insert]\njk = enter_normal_mode"),
+            "Line 2: Multi-key bindings are not supported in insert mode"
+        );
+    }
+}
@@ -2,11 +2,14 @@ use tui::layout::{Direction, Group, Rect, Size};
 use tui::style::*;
 use tui::widgets::*;
 
-use components::App;
+use components::{App, Help};
 use models::{AppState, Mode};
 use widgets::{self, ChatHistory};
 use TerminalBackend;
 
+const MAX_INPUT_HEIGHT: usize = 5;
+const MAX_COMPLETION_CANDIDATES: usize = 8;
+
 pub fn render(app: &App, terminal: &mut TerminalBackend, size: &Rect) {
     Group::default()
         .direction(Direction::Horizontal)
@@ -14,45 +17,76 @@ pub fn render(app: &App, terminal: &mut TerminalBackend, size: &Rect) {
         .sizes(&[Size::Percent(20), Size::Percent(80)])
         .render(terminal, size, |terminal, chunks| {
             render_sidebar(app.state(), terminal, &chunks[0]);
-            render_main(app.state(), terminal, &chunks[1]);
+            render_main(app, terminal, &chunks[1]);
         });
 
     if app.state().current_mode() == &Mode::SelectChannel {
-        let mut selector_rect = size.clone();
-        // Pick the largest out of 50% and X cells in both directions, but also cap it to display
-        // size if it's smaller than the intended minimum.
-        selector_rect.width = (size.width / 2).max(40).min(size.width);
-        selector_rect.height = (size.height / 2).max(20).min(size.height);
-        // Center selector in the middle of parent size
-        selector_rect.x = (size.x + size.width / 2) - (selector_rect.width / 2);
-        selector_rect.y = (size.y + size.height / 2) - (selector_rect.height / 2);
-
-        render_channel_selector(&app, terminal, &selector_rect);
+        render_channel_selector(&app, terminal, &centered_rect(size));
+    }
+
+    if let Some(ref help) = app.help {
+        render_help(help, app.state().current_mode(), terminal, &help_rect(size));
     }
 }
 
+fn centered_rect(size: &Rect) -> Rect {
+    let mut rect = size.clone();
+    // Pick the largest out of 50% and X cells in both directions, but also cap it to display
+    // size if it's smaller than the intended minimum.
+    rect.width = (size.width / 2).max(40).min(size.width);
+    rect.height = (size.height / 2).max(20).min(size.height);
+    // Center in the middle of parent size
+    rect.x = (size.x + size.width / 2) - (rect.width / 2);
+    rect.y = (size.y + size.height / 2) - (rect.height / 2);
+    rect
+}
+
+/// Like `centered_rect`, but using the full height so as many key bindings as possible fit.
+fn help_rect(size: &Rect) -> Rect {
+    let mut rect = centered_rect(size);
+    rect.y = size.y;
+    rect.height = size.height;
+    rect
+}
+
 fn render_sidebar(state: &AppState, terminal: &mut TerminalBackend, rect: &Rect) {
     let mut block = Block::default().borders(Borders::RIGHT);
     block.render(terminal, rect);
 
-    widgets::ChannelList::new(&state.channels, state.selected_channel_id())
+    widgets::ChannelList::new(&state.channels, state.selected_channel_id(), &state.unreads)
         .render(terminal, &block.inner(rect));
 }
 
-fn render_main(state: &AppState, terminal: &mut TerminalBackend, rect: &Rect) {
+fn render_main(app: &App, terminal: &mut TerminalBackend, rect: &Rect) {
+    let state = app.state();
+    // Grow input with the composed text, up to a limit.
+    let input_height = match state.current_mode() {
+        &Mode::Command => 1,
+        _ => app.composer.line_count().max(1).min(MAX_INPUT_HEIGHT) as u16,
+    };
+
     Group::default()
         .direction(Direction::Vertical)
         .sizes(&[
             Size::Fixed(1),
             Size::Min(10),
             Size::Fixed(1),
-            Size::Fixed(1),
+            Size::Fixed(input_height),
         ])
         .render(terminal, rect, |terminal, chunks| {
             render_breadcrumbs(state, terminal, &chunks[0]);
-            render_history(state, terminal, &chunks[1]);
-            render_statusbar(state, terminal, &chunks[2]);
-            render_input(state, terminal, &chunks[3]);
+            if state.selected_thread.is_some() {
+                render_history_with_thread(state, terminal, &chunks[1]);
+            } else {
+                render_history(state, terminal, &chunks[1]);
+            }
+            render_statusbar(app, terminal, &chunks[2]);
+            if state.current_mode() == &Mode::Command {
+                render_command_line(app, terminal, &chunks[3]);
+            } else {
+                render_input(app, terminal, &chunks[3]);
+            }
+            render_completion(app, terminal, &chunks[3]);
         });
 }
 
@@ -65,8 +99,9 @@ fn render_breadcrumbs(state: &AppState, terminal: &mut TerminalBackend, rect: &R
             };
             Paragraph::default()
                 .text(&format!(
-                    "{{mod=bold {team}}} > {{mod=bold #{channel}}} [{topic}]",
+                    "{{mod=bold {team}}} > {{mod=bold {sigil}{channel}}} [{topic}]",
                     team = state.team_name,
+                    sigil = channel.sigil(),
                     channel = channel.name(),
                     topic = topic
                 ))
@@ -95,30 +130,166 @@ fn render_history(state: &AppState, terminal: &mut TerminalBackend, rect: &Rect)
         .render(terminal, rect);
 }
 
-fn render_statusbar(state: &AppState, terminal: &mut TerminalBackend, rect: &Rect) {
-    let (mode, mode_color) = match state.current_mode {
-        Mode::History => ("HISTORY", "bg=cyan;fg=black"),
-        Mode::SelectChannel => ("CHANNELS", "bg=black;fg=white"),
+fn render_history_with_thread(state: &AppState, terminal: &mut TerminalBackend, rect: &Rect) {
+    Group::default()
+        .direction(Direction::Horizontal)
+        .sizes(&[Size::Percent(60), Size::Percent(40)])
+        .render(terminal, rect, |terminal, chunks| {
+            render_history(state, terminal, &chunks[0]);
+            render_thread(state, terminal, &chunks[1]);
+        });
+}
+
+fn render_thread(state: &AppState, terminal: &mut TerminalBackend, rect: &Rect) {
+    let mut block = Block::default()
+        .title("Thread")
+        .borders(Borders::LEFT | Borders::TOP);
+    block.render(terminal, rect);
+
+    let inner = block.inner(rect);
+    if inner.width < 2 {
+        return;
+    }
+
+    // Leave one width for scrollbar. Threads always show their newest replies.
+    if let Some(canvas) = state.render_thread_canvas(inner.width - 1) {
+        ChatHistory::with_canvas(&canvas).render(terminal, &inner);
+    }
+}
+
+fn render_statusbar(app: &App, terminal: &mut TerminalBackend, rect: &Rect) {
+    let state = app.state();
+    let mode = mode_label(&state.current_mode);
+    let mode_color = match state.current_mode {
+        Mode::History => "bg=cyan;fg=black",
+        Mode::Normal => "bg=yellow;fg=black",
+        Mode::Insert => "bg=green;fg=black",
+        Mode::SelectChannel => "bg=black;fg=white",
+        Mode::Command => "bg=magenta;fg=black",
+    };
+    let thread = match (state.selected_thread.is_some(), app.broadcast_reply) {
+        (true, true) => " [thread, also to channel]",
+        (true, false) => " [thread]",
+        (false, _) => "",
+    };
+    let editing = if app.editing.is_some() {
+        " [editing]"
+    } else {
+        ""
+    };
+    let status = format!(
+        " - [{offset}/{height}]{thread}{editing} ",
+        offset = state.history_scroll,
+        height = state.max_history_scroll(),
+        thread = thread,
+        editing = editing,
+    );
+    let prompt = if app.pending_deletion.is_some() {
+        "{fg=red Delete the selected message? (y/n)}"
+    } else {
+        ""
     };
+    let style = Style::default().bg(Color::Gray).fg(Color::White);
     Paragraph::default()
-        .text(&format!(
-            "{{{mode_color} {mode}}} - [{offset}/{height}]",
-            mode = mode,
-            mode_color = mode_color,
-            offset = state.history_scroll,
-            height = state.max_history_scroll(),
-        ))
-        .style(Style::default().bg(Color::Gray).fg(Color::White))
+        .text(&format!("{{{} {}}}{}{}", mode_color, mode, status, prompt))
+        .style(style)
         .render(terminal, rect);
+
+    // Pending keys can contain any character from the user's keymap, including the braces of
+    // Paragraph markup, so they are drawn as plain text after the rest.
+    let keys_x = rect.left() + (mode.len() + status.len()) as u16;
+    if prompt.is_empty() && !app.pending_keys.is_empty() && keys_x < rect.right() {
+        let keys_rect = Rect::new(keys_x, rect.top(), rect.right() - keys_x, rect.height);
+        List::new(vec![Item::StyledData(&app.pending_keys, &style)].into_iter())
+            .style(style)
+            .render(terminal, &keys_rect);
+    }
 }
 
-fn render_input(_state: &AppState, terminal: &mut TerminalBackend, rect: &Rect) {
-    Paragraph::default()
-        .text("{fg=dark_gray Enter a reply...}")
+fn mode_label(mode: &Mode) -> &'static str {
+    match *mode {
+        Mode::History => "HISTORY",
+        Mode::Normal => "NORMAL",
+        Mode::Insert => "INSERT",
+        Mode::SelectChannel => "CHANNELS",
+        Mode::Command => "COMMAND",
+    }
+}
+
+fn render_input(app: &App, terminal: &mut TerminalBackend, rect: &Rect) {
+    let cursor = match app.state().current_mode() {
+        &Mode::Normal | &Mode::Insert => Some(app.composer.cursor_line_and_column()),
+        _ => None,
+    };
+
+    let placeholder = match (&app.editing, &app.state().selected_thread) {
+        (&Some(_), _) => "Edit message...",
+        (&None, &Some(_)) => "Reply in thread...",
+        (&None, &None) => "Enter a reply...",
+    };
+
+    widgets::TextEdit::default()
+        .text(app.composer.text())
+        .placeholder(placeholder)
+        .cursor(cursor)
+        .style(Style::default().bg(Color::Black).fg(Color::White))
+        .render(terminal, rect);
+}
+
+fn render_command_line(app: &App, terminal: &mut TerminalBackend, rect: &Rect) {
+    let text = format!(":{}", app.command_line.text());
+    widgets::LineEdit::default()
+        .text(&text)
+        .cursor_pos(app.command_line.cursor_line_and_column().1 + 1)
         .style(Style::default().bg(Color::Black).fg(Color::White))
         .render(terminal, rect);
 }
 
+/// Renders a popup of completion candidates right above the input.
+fn render_completion(app: &App, terminal: &mut TerminalBackend, input_rect: &Rect) {
+    let completion = match app.completion {
+        Some(ref completion) => completion,
+        None => return,
+    };
+
+    let candidates = completion.candidates();
+    let longest = candidates
+        .iter()
+        .map(|candidate| candidate.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    // Add two for borders in both directions.
+    let width = (longest as u16 + 2).min(input_rect.width);
+    let height = (candidates.len().min(MAX_COMPLETION_CANDIDATES) as u16 + 2).min(input_rect.y);
+    if width <= 2 || height <= 2 {
+        return;
+    }
+
+    let rect = Rect::new(input_rect.x, input_rect.y - height, width, height);
+    let black_on_gray = Style::default().bg(Color::Gray).fg(Color::Black);
+    let white_on_black = Style::default().bg(Color::Black).fg(Color::White);
+
+    // SelectableList does not render background style. Pad items with spaces instead.
+    let items: Vec<String> = candidates
+        .iter()
+        .map(|candidate| format!("{:<1$}", candidate, (width - 2) as usize))
+        .collect();
+
+    SelectableList::default()
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(black_on_gray)
+                .style(black_on_gray),
+        )
+        .style(black_on_gray)
+        .highlight_style(white_on_black)
+        .items(&items)
+        .select(completion.selected_index())
+        .render(terminal, &rect);
+}
+
 fn render_channel_selector(app: &App, terminal: &mut TerminalBackend, rect: &Rect) {
     if rect.width <= 5 || rect.height <= 5 {
         return;
@@ -157,7 +328,14 @@ fn render_channel_selector(app: &App, terminal: &mut TerminalBackend, rect: &Rec
         .channel_selector
         .top_matches(&app.state().channels, list_rect.height as usize)
         .into_iter()
-        .map(|m| format!("#{:<1$}", m.channel.name(), list_rect.width as usize))
+        .map(|m| {
+            format!(
+                "{}{:<2$}",
+                m.channel.sigil(),
+                m.channel.name(),
+                list_rect.width as usize
+            )
+        })
         .collect();
 
     SelectableList::default()
@@ -173,3 +351,50 @@ fn render_channel_selector(app: &App, terminal: &mut TerminalBackend, rect: &Rec
         .select(app.channel_selector.selected_index(matches.len()))
         .render(terminal, &list_rect);
 }
+
+fn render_help(help: &Help, mode: &Mode, terminal: &mut TerminalBackend, rect: &Rect) {
+    if rect.width <= 5 || rect.height <= 5 {
+        return;
+    }
+
+    // Leave room for the borders.
+    help.set_page_height((rect.height - 2) as usize);
+
+    let black_on_gray = Style::default().bg(Color::Gray).fg(Color::Black);
+    let entries = help.entries();
+    let title = if help.is_scrollable() {
+        let last_shown = (help.scroll() + help.page_height()).min(entries.len());
+        format!(
+            "Keys in {} mode, {}-{} of {} (j/k to scroll)",
+            mode_label(mode),
+            help.scroll() + 1,
+            last_shown,
+            entries.len()
+        )
+    } else {
+        format!("Keys in {} mode (press any key to close)", mode_label(mode))
+    };
+    let keys_width = entries.iter().map(|&(ref keys, _)| keys.len()).max().unwrap_or(0);
+
+    // SelectableList does not render background style. Pad items with spaces instead.
+    let items: Vec<String> = entries[help.scroll()..]
+        .iter()
+        .map(|&(ref keys, description)| {
+            let line = format!("{:<2$}  {}", keys, description, keys_width);
+            format!("{:<1$}", line, (rect.width - 2) as usize)
+        })
+        .collect();
+
+    SelectableList::default()
+        .block(
+            Block::default()
+                .title(&title)
+                .borders(Borders::ALL)
+                .border_style(black_on_gray)
+                .title_style(black_on_gray)
+                .style(black_on_gray),
+        )
+        .style(black_on_gray)
+        .items(&items)
+        .render(terminal, rect);
+}
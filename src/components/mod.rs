@@ -1,11 +1,21 @@
 mod app;
 mod channel_selector;
+mod command;
+mod completion;
+mod composer;
+mod help;
 mod layout;
 
 pub mod event_loop;
 pub mod input_manager;
+pub mod keymap;
 
 pub use self::app::*;
 pub use self::channel_selector::*;
+pub use self::command::Command;
+pub use self::completion::*;
+pub use self::composer::*;
+pub use self::help::Help;
 pub use self::input_manager::KeyManager;
+pub use self::keymap::Keymap;
 pub use self::layout::*;
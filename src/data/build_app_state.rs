@@ -1,8 +1,13 @@
 use failure::Error;
+use slack::api;
 use slack::api::rtm::StartResponse;
 use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
 
-use models::{AppState, Channel, ChannelList, MessageBuffer, Mode, User, UserList};
+use models::{
+    AppState, Channel, ChannelID, ChannelList, MessageBuffer, MessageID, Mode, UnreadCount, User,
+    UserID, UserList,
+};
 
 pub fn build_app_state(response: &StartResponse) -> Result<AppState, Error> {
     let users: UserList = response
@@ -13,12 +18,41 @@ pub fn build_app_state(response: &StartResponse) -> Result<AppState, Error> {
         .flat_map(User::from_slack)
         .collect();
 
-    let channels: ChannelList = response
+    let current_user_id = response
+        .slf
+        .as_ref()
+        .and_then(|slf| slf.id.as_ref())
+        .map(UserID::from)
+        .ok_or_else(|| format_err!("Slack did not provide the current user on login"))?;
+
+    let slack_channels = response
         .channels
         .clone()
-        .expect("Slack did not provide a channel list on login")
+        .expect("Slack did not provide a channel list on login");
+    // Group DMs are listed as groups too, but they are already imported from the mpims.
+    let slack_groups: Vec<api::Group> = response
+        .groups
+        .iter()
+        .flat_map(|groups| groups.iter())
+        .filter(|group| !group.is_mpim.unwrap_or(false))
+        .cloned()
+        .collect();
+    let direct_messages = response
+        .ims
+        .iter()
+        .flat_map(|ims| ims.iter())
+        .filter(|im| im.is_user_deleted != Some(true))
+        .flat_map(|im| Channel::from_slack_im(im, &users));
+    let slack_mpims: &[api::Mpim] = response.mpims.as_ref().map_or(&[], |mpims| &mpims[..]);
+    let group_direct_messages = slack_mpims
+        .iter()
+        .flat_map(|mpim| Channel::from_slack_mpim(mpim, &users, &current_user_id));
+    let channels: ChannelList = slack_channels
         .iter()
         .flat_map(Channel::from_slack)
+        .chain(slack_groups.iter().flat_map(Channel::from_slack_group))
+        .chain(direct_messages)
+        .chain(group_direct_messages)
         .collect();
 
     // TODO: Pick a channel using a more intelligent way...
@@ -31,6 +65,34 @@ pub fn build_app_state(response: &StartResponse) -> Result<AppState, Error> {
         None => return Err(format_err!("Could not find any channels in the Team")),
     };
 
+    let read_states: Vec<ReadState> = slack_channels
+        .iter()
+        .filter_map(ReadState::from_channel)
+        .chain(slack_groups.iter().filter_map(ReadState::from_group))
+        .chain(slack_mpims.iter().filter_map(ReadState::from_mpim))
+        .collect();
+    let mut unreads: HashMap<ChannelID, UnreadCount> = read_states
+        .iter()
+        .filter(|state| state.unread_count > 0)
+        .map(|state| {
+            let unread = UnreadCount {
+                messages: state.unread_count,
+                mentions: 0,
+            };
+            (state.channel_id.clone(), unread)
+        })
+        .collect();
+    let last_read: HashMap<ChannelID, MessageID> = read_states
+        .into_iter()
+        .filter_map(|state| Some((state.channel_id, state.last_read?)))
+        .collect();
+
+    // The selected channel is being viewed, so it is read as soon as the app starts.
+    let new_messages_after = match unreads.remove(&selected_channel_id) {
+        Some(_) => last_read.get(&selected_channel_id).cloned(),
+        None => None,
+    };
+
     let team_name = response
         .team
         .as_ref()
@@ -44,14 +106,131 @@ pub fn build_app_state(response: &StartResponse) -> Result<AppState, Error> {
         chat_canvas: RefCell::new(None),
         history_scroll: 0,
         last_chat_height: Cell::new(0),
+        new_messages_row: Cell::new(None),
+        scroll_to_new_messages: new_messages_after.is_some(),
+        message_rows: RefCell::new(Vec::new()),
+        selected_message: None,
+        scroll_to_selected_message: false,
 
         selected_channel_id,
         channels,
+        unreads,
+        last_read,
+        new_messages_after,
+        selected_thread: None,
 
         is_loading_more_messages: false,
+        failed_history_channels: HashSet::new(),
         messages: MessageBuffer::new(),
 
         team_name,
+        current_user_id,
         users,
     })
 }
+
+/// How far the user has read in a channel, as reported by Slack on login.
+struct ReadState {
+    channel_id: ChannelID,
+    last_read: Option<MessageID>,
+    unread_count: usize,
+}
+
+impl ReadState {
+    fn from_channel(channel: &api::Channel) -> Option<Self> {
+        Some(ReadState {
+            channel_id: ChannelID::from(channel.id.as_ref()?),
+            last_read: channel.last_read.clone().map(MessageID::from),
+            unread_count: channel
+                .unread_count_display
+                .or(channel.unread_count)
+                .unwrap_or(0) as usize,
+        })
+    }
+
+    fn from_group(group: &api::Group) -> Option<Self> {
+        Some(ReadState {
+            channel_id: ChannelID::from(group.id.as_ref()?),
+            last_read: group.last_read.clone().map(MessageID::from),
+            unread_count: group
+                .unread_count_display
+                .or(group.unread_count)
+                .unwrap_or(0) as usize,
+        })
+    }
+
+    fn from_mpim(mpim: &api::Mpim) -> Option<Self> {
+        Some(ReadState {
+            channel_id: ChannelID::from(mpim.id.as_ref()?),
+            last_read: mpim.last_read.clone().map(MessageID::from),
+            unread_count: mpim
+                .unread_count_display
+                .or(mpim.unread_count)
+                .unwrap_or(0) as usize,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use models::ChannelKind;
+    use serde_json;
+
+    #[test]
+    fn it_imports_group_direct_messages_only_from_mpims() {
+        let response: StartResponse = serde_json::from_str(
+            r#"{
+                "ok": true,
+                "self": {"id": "U1"},
+                "team": {"name": "Team"},
+                "users": [{"id": "U1", "name": "me"}, {"id": "U2", "name": "carol"}],
+                "channels": [{"id": "C1", "name": "general", "is_member": true}],
+                "groups": [
+                    {"id": "G1", "name": "secret", "is_mpim": false, "unread_count": 1},
+                    {"id": "G2", "name": "mpdm-me--carol-1", "is_mpim": true, "unread_count": 2}
+                ],
+                "mpims": [{"id": "G2", "members": ["U1", "U2"], "unread_count_display": 3}]
+            }"#,
+        )
+        .unwrap();
+
+        let state = build_app_state(&response).unwrap();
+
+        let private = state.channels.get(&ChannelID::from("G1")).unwrap();
+        assert_eq!(private.kind(), ChannelKind::Private);
+        let group_dm = state.channels.get(&ChannelID::from("G2")).unwrap();
+        assert_eq!(group_dm.kind(), ChannelKind::GroupDirectMessage);
+        assert_eq!(group_dm.name(), "carol");
+
+        assert_eq!(state.unreads[&ChannelID::from("G1")].messages, 1);
+        assert_eq!(state.unreads[&ChannelID::from("G2")].messages, 3);
+    }
+
+    #[test]
+    fn it_skips_direct_messages_with_deleted_users() {
+        let response: StartResponse = serde_json::from_str(
+            r#"{
+                "ok": true,
+                "self": {"id": "U1"},
+                "team": {"name": "Team"},
+                "users": [
+                    {"id": "U1", "name": "me"},
+                    {"id": "U2", "name": "carol"},
+                    {"id": "U3", "name": "dave", "deleted": true}
+                ],
+                "channels": [{"id": "C1", "name": "general", "is_member": true}],
+                "ims": [
+                    {"id": "D2", "user": "U2", "is_user_deleted": false},
+                    {"id": "D3", "user": "U3", "is_user_deleted": true}
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let state = build_app_state(&response).unwrap();
+
+        assert!(state.channels.get(&ChannelID::from("D2")).is_some());
+        assert!(state.channels.get(&ChannelID::from("D3")).is_none());
+    }
+}
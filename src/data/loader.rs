@@ -1,23 +1,93 @@
 extern crate slack;
+use serde_json::{self, Value};
 use slack::api;
+use slack::api::requests::SlackWebRequestSender;
 use std::sync::mpsc;
 use std::thread;
 
 use failure::Error;
 
-use models::{ChannelID, MessageID};
+use models::{ChannelID, ChannelKind, MessageID};
 
 #[derive(Debug)]
 enum Task {
-    ChannelHistory(ChannelID, Option<MessageID>),
+    ChannelHistory(ChannelID, ChannelKind, Option<MessageID>),
+    PostMessage(ChannelID, String),
+    /// Posts a reply in a thread, optionally sending it to the channel too.
+    PostReply(ChannelID, MessageID, String, bool),
+    ThreadReplies(ChannelID, ChannelKind, MessageID),
+    UpdateMessage(ChannelID, MessageID, String),
+    DeleteMessage(ChannelID, MessageID),
+    JoinChannel(String),
+    LeaveChannel(ChannelID, ChannelKind),
+    SetTopic(ChannelID, ChannelKind, String),
+    SetPurpose(ChannelID, ChannelKind, String),
+    MarkChannel(ChannelID, ChannelKind, MessageID),
+    AddReaction(ChannelID, MessageID, String),
+    RemoveReaction(ChannelID, MessageID, String),
+    LoadStars,
+    StarChannel(ChannelID),
+    UnstarChannel(ChannelID),
 }
 
 #[derive(Debug)]
 pub enum TaskResult {
-    ChannelHistory(
+    ChannelHistory(ChannelID, Result<History, Error>),
+    ThreadReplies(ChannelID, MessageID, Result<Vec<Value>, Error>),
+    PostMessage(
         ChannelID,
-        Result<api::channels::HistoryResponse, api::channels::HistoryError<api::requests::Error>>,
+        Result<api::chat::PostMessageResponse, api::chat::PostMessageError<api::requests::Error>>,
     ),
+    UpdateMessage(
+        ChannelID,
+        Result<api::chat::UpdateResponse, api::chat::UpdateError<api::requests::Error>>,
+    ),
+    DeleteMessage(
+        ChannelID,
+        Result<api::chat::DeleteResponse, api::chat::DeleteError<api::requests::Error>>,
+    ),
+    /// The reply as Slack stored it, if it could be sent.
+    PostReply(ChannelID, Result<Value, Error>),
+    JoinChannel(
+        String,
+        Result<api::channels::JoinResponse, api::channels::JoinError<api::requests::Error>>,
+    ),
+    LeaveChannel(ChannelID, Result<Value, Error>),
+    SetTopic(ChannelID, Result<Value, Error>),
+    SetPurpose(ChannelID, Result<Value, Error>),
+    MarkChannel(ChannelID, Result<(), Error>),
+    /// The message and name of the reaction, and whether it could be added.
+    AddReaction(
+        ChannelID,
+        MessageID,
+        String,
+        Result<api::reactions::AddResponse, api::reactions::AddError<api::requests::Error>>,
+    ),
+    RemoveReaction(
+        ChannelID,
+        MessageID,
+        String,
+        Result<api::reactions::RemoveResponse, api::reactions::RemoveError<api::requests::Error>>,
+    ),
+    Stars(Result<api::stars::ListResponse, api::stars::ListError<api::requests::Error>>),
+    StarChannel(
+        ChannelID,
+        Result<api::stars::AddResponse, api::stars::AddError<api::requests::Error>>,
+    ),
+    UnstarChannel(
+        ChannelID,
+        Result<api::stars::RemoveResponse, api::stars::RemoveError<api::requests::Error>>,
+    ),
+}
+
+/// A page of history, loaded from the endpoint matching the kind of channel.
+///
+/// Messages are kept as JSON, as `api::Message` does not have reply counts and cannot parse
+/// thread replies that were also sent to the channel.
+#[derive(Debug)]
+pub struct History {
+    pub messages: Vec<Value>,
+    pub has_more: Option<bool>,
 }
 
 struct BackgroundLoader {
@@ -57,15 +127,179 @@ impl Loader {
     pub fn load_channel_history(
         &mut self,
         channel_id: &ChannelID,
+        kind: ChannelKind,
         before_message_id: Option<&MessageID>,
     ) -> Result<(), Error> {
         self.requests
             .send(Task::ChannelHistory(
                 channel_id.clone(),
+                kind,
                 before_message_id.cloned(),
             ))
             .map_err(|e| e.into())
     }
+
+    pub fn post_message(&mut self, channel_id: &ChannelID, text: String) -> Result<(), Error> {
+        self.requests
+            .send(Task::PostMessage(channel_id.clone(), text))
+            .map_err(|e| e.into())
+    }
+
+    pub fn post_reply(
+        &mut self,
+        channel_id: &ChannelID,
+        thread_id: &MessageID,
+        text: String,
+        broadcast: bool,
+    ) -> Result<(), Error> {
+        self.requests
+            .send(Task::PostReply(
+                channel_id.clone(),
+                thread_id.clone(),
+                text,
+                broadcast,
+            ))
+            .map_err(|e| e.into())
+    }
+
+    pub fn load_thread_replies(
+        &mut self,
+        channel_id: &ChannelID,
+        kind: ChannelKind,
+        thread_id: &MessageID,
+    ) -> Result<(), Error> {
+        self.requests
+            .send(Task::ThreadReplies(
+                channel_id.clone(),
+                kind,
+                thread_id.clone(),
+            ))
+            .map_err(|e| e.into())
+    }
+
+    pub fn update_message(
+        &mut self,
+        channel_id: &ChannelID,
+        message_id: &MessageID,
+        text: String,
+    ) -> Result<(), Error> {
+        self.requests
+            .send(Task::UpdateMessage(
+                channel_id.clone(),
+                message_id.clone(),
+                text,
+            ))
+            .map_err(|e| e.into())
+    }
+
+    pub fn delete_message(
+        &mut self,
+        channel_id: &ChannelID,
+        message_id: &MessageID,
+    ) -> Result<(), Error> {
+        self.requests
+            .send(Task::DeleteMessage(channel_id.clone(), message_id.clone()))
+            .map_err(|e| e.into())
+    }
+
+    pub fn join_channel(&mut self, name: String) -> Result<(), Error> {
+        self.requests
+            .send(Task::JoinChannel(name))
+            .map_err(|e| e.into())
+    }
+
+    pub fn leave_channel(
+        &mut self,
+        channel_id: &ChannelID,
+        kind: ChannelKind,
+    ) -> Result<(), Error> {
+        self.requests
+            .send(Task::LeaveChannel(channel_id.clone(), kind))
+            .map_err(|e| e.into())
+    }
+
+    pub fn set_topic(
+        &mut self,
+        channel_id: &ChannelID,
+        kind: ChannelKind,
+        topic: String,
+    ) -> Result<(), Error> {
+        self.requests
+            .send(Task::SetTopic(channel_id.clone(), kind, topic))
+            .map_err(|e| e.into())
+    }
+
+    pub fn set_purpose(
+        &mut self,
+        channel_id: &ChannelID,
+        kind: ChannelKind,
+        purpose: String,
+    ) -> Result<(), Error> {
+        self.requests
+            .send(Task::SetPurpose(channel_id.clone(), kind, purpose))
+            .map_err(|e| e.into())
+    }
+
+    pub fn mark_channel(
+        &mut self,
+        channel_id: &ChannelID,
+        kind: ChannelKind,
+        message_id: &MessageID,
+    ) -> Result<(), Error> {
+        self.requests
+            .send(Task::MarkChannel(
+                channel_id.clone(),
+                kind,
+                message_id.clone(),
+            ))
+            .map_err(|e| e.into())
+    }
+
+    pub fn add_reaction(
+        &mut self,
+        channel_id: &ChannelID,
+        message_id: &MessageID,
+        name: String,
+    ) -> Result<(), Error> {
+        self.requests
+            .send(Task::AddReaction(
+                channel_id.clone(),
+                message_id.clone(),
+                name,
+            ))
+            .map_err(|e| e.into())
+    }
+
+    pub fn remove_reaction(
+        &mut self,
+        channel_id: &ChannelID,
+        message_id: &MessageID,
+        name: String,
+    ) -> Result<(), Error> {
+        self.requests
+            .send(Task::RemoveReaction(
+                channel_id.clone(),
+                message_id.clone(),
+                name,
+            ))
+            .map_err(|e| e.into())
+    }
+
+    pub fn load_stars(&mut self) -> Result<(), Error> {
+        self.requests.send(Task::LoadStars).map_err(|e| e.into())
+    }
+
+    pub fn star_channel(&mut self, channel_id: &ChannelID) -> Result<(), Error> {
+        self.requests
+            .send(Task::StarChannel(channel_id.clone()))
+            .map_err(|e| e.into())
+    }
+
+    pub fn unstar_channel(&mut self, channel_id: &ChannelID) -> Result<(), Error> {
+        self.requests
+            .send(Task::UnstarChannel(channel_id.clone()))
+            .map_err(|e| e.into())
+    }
 }
 
 impl BackgroundLoader {
@@ -82,6 +316,30 @@ impl BackgroundLoader {
         })
     }
 
+    /// Calls a Web API method and returns the response as JSON, for when the types in `slack_api`
+    /// would lose parts of the response.
+    fn call_method<'a>(
+        &'a self,
+        method: &str,
+        mut params: Vec<(&str, &'a str)>,
+    ) -> Result<Value, Error> {
+        params.push(("token", self.slack_api_key.as_str()));
+        let body = self
+            .client
+            .send(&format!("https://slack.com/api/{}", method), &params)?;
+        let response: Value = serde_json::from_str(&body)?;
+
+        if response["ok"].as_bool() == Some(true) {
+            Ok(response)
+        } else {
+            Err(format_err!(
+                "{} failed: {}",
+                method,
+                response["error"].as_str().unwrap_or("unknown error")
+            ))
+        }
+    }
+
     fn run(&mut self) {
         loop {
             let task = match self.requests.recv() {
@@ -90,9 +348,40 @@ impl BackgroundLoader {
             };
 
             match task {
-                Task::ChannelHistory(channel_id, before_message_id) => {
-                    self.load_channel_history(channel_id, before_message_id)
+                Task::ChannelHistory(channel_id, kind, before_message_id) => {
+                    self.load_channel_history(channel_id, kind, before_message_id)
+                }
+                Task::PostMessage(channel_id, text) => self.post_message(channel_id, text),
+                Task::PostReply(channel_id, thread_id, text, broadcast) => {
+                    self.post_reply(channel_id, thread_id, text, broadcast)
+                }
+                Task::ThreadReplies(channel_id, kind, thread_id) => {
+                    self.load_thread_replies(channel_id, kind, thread_id)
+                }
+                Task::UpdateMessage(channel_id, message_id, text) => {
+                    self.update_message(channel_id, message_id, text)
+                }
+                Task::DeleteMessage(channel_id, message_id) => {
+                    self.delete_message(channel_id, message_id)
+                }
+                Task::JoinChannel(name) => self.join_channel(name),
+                Task::LeaveChannel(channel_id, kind) => self.leave_channel(channel_id, kind),
+                Task::SetTopic(channel_id, kind, topic) => self.set_topic(channel_id, kind, topic),
+                Task::SetPurpose(channel_id, kind, purpose) => {
+                    self.set_purpose(channel_id, kind, purpose)
+                }
+                Task::MarkChannel(channel_id, kind, message_id) => {
+                    self.mark_channel(channel_id, kind, message_id)
+                }
+                Task::AddReaction(channel_id, message_id, name) => {
+                    self.add_reaction(channel_id, message_id, name)
                 }
+                Task::RemoveReaction(channel_id, message_id, name) => {
+                    self.remove_reaction(channel_id, message_id, name)
+                }
+                Task::LoadStars => self.load_stars(),
+                Task::StarChannel(channel_id) => self.star_channel(channel_id),
+                Task::UnstarChannel(channel_id) => self.unstar_channel(channel_id),
             }
         }
     }
@@ -100,21 +389,292 @@ impl BackgroundLoader {
     fn load_channel_history(
         &mut self,
         channel_id: ChannelID,
+        kind: ChannelKind,
         before_message_id: Option<MessageID>,
     ) {
-        let latest = before_message_id.map(|id| id.as_string());
-        let response = slack::api::channels::history(
+        let mut params = vec![("channel", channel_id.as_str())];
+        if let Some(ref before_message_id) = before_message_id {
+            params.push(("latest", before_message_id.as_str()));
+        }
+
+        let response = self
+            .call_method(&format!("{}.history", method_prefix(kind)), params)
+            .map(|response| History {
+                messages: messages_in(&response),
+                has_more: response["has_more"].as_bool(),
+            });
+        self.results
+            .send(TaskResult::ChannelHistory(channel_id, response))
+            .ok();
+    }
+
+    fn post_message(&mut self, channel_id: ChannelID, text: String) {
+        let response = slack::api::chat::post_message(
             &self.client,
             &self.slack_api_key,
-            &slack::api::channels::HistoryRequest {
+            &slack::api::chat::PostMessageRequest {
                 channel: channel_id.as_str(),
-                latest: latest.as_ref().map(String::as_ref),
+                text: &text,
+                as_user: Some(true),
 
                 ..Default::default()
             },
         );
         self.results
-            .send(TaskResult::ChannelHistory(channel_id, response))
+            .send(TaskResult::PostMessage(channel_id, response))
+            .ok();
+    }
+
+    fn post_reply(
+        &mut self,
+        channel_id: ChannelID,
+        thread_id: MessageID,
+        text: String,
+        broadcast: bool,
+    ) {
+        let params = vec![
+            ("channel", channel_id.as_str()),
+            ("text", text.as_str()),
+            ("as_user", "true"),
+            ("thread_ts", thread_id.as_str()),
+            ("reply_broadcast", if broadcast { "true" } else { "false" }),
+        ];
+        let response = self
+            .call_method("chat.postMessage", params)
+            .map(|response| response["message"].clone());
+        self.results
+            .send(TaskResult::PostReply(channel_id, response))
+            .ok();
+    }
+
+    fn update_message(&mut self, channel_id: ChannelID, message_id: MessageID, text: String) {
+        let response = slack::api::chat::update(
+            &self.client,
+            &self.slack_api_key,
+            &slack::api::chat::UpdateRequest {
+                ts: message_id.as_str(),
+                channel: channel_id.as_str(),
+                text: &text,
+                as_user: Some(true),
+
+                ..Default::default()
+            },
+        );
+        self.results
+            .send(TaskResult::UpdateMessage(channel_id, response))
+            .ok();
+    }
+
+    fn delete_message(&mut self, channel_id: ChannelID, message_id: MessageID) {
+        let response = slack::api::chat::delete(
+            &self.client,
+            &self.slack_api_key,
+            &slack::api::chat::DeleteRequest {
+                ts: message_id.as_str(),
+                channel: channel_id.as_str(),
+                as_user: Some(true),
+            },
+        );
+        self.results
+            .send(TaskResult::DeleteMessage(channel_id, response))
+            .ok();
+    }
+
+    fn load_thread_replies(
+        &mut self,
+        channel_id: ChannelID,
+        kind: ChannelKind,
+        thread_id: MessageID,
+    ) {
+        let params = vec![
+            ("channel", channel_id.as_str()),
+            ("thread_ts", thread_id.as_str()),
+        ];
+        let response = self
+            .call_method(&format!("{}.replies", method_prefix(kind)), params)
+            .map(|response| messages_in(&response));
+        self.results
+            .send(TaskResult::ThreadReplies(channel_id, thread_id, response))
+            .ok();
+    }
+
+    fn join_channel(&mut self, name: String) {
+        let response = slack::api::channels::join(
+            &self.client,
+            &self.slack_api_key,
+            &slack::api::channels::JoinRequest {
+                name: &name,
+
+                ..Default::default()
+            },
+        );
+        self.results
+            .send(TaskResult::JoinChannel(name, response))
+            .ok();
+    }
+
+    fn leave_channel(&mut self, channel_id: ChannelID, kind: ChannelKind) {
+        let response = self.call_method(
+            &format!("{}.leave", method_prefix(kind)),
+            vec![("channel", channel_id.as_str())],
+        );
+        self.results
+            .send(TaskResult::LeaveChannel(channel_id, response))
+            .ok();
+    }
+
+    fn set_topic(&mut self, channel_id: ChannelID, kind: ChannelKind, topic: String) {
+        let response = self.call_method(
+            &format!("{}.setTopic", method_prefix(kind)),
+            vec![("channel", channel_id.as_str()), ("topic", &topic)],
+        );
+        self.results
+            .send(TaskResult::SetTopic(channel_id, response))
+            .ok();
+    }
+
+    fn set_purpose(&mut self, channel_id: ChannelID, kind: ChannelKind, purpose: String) {
+        let response = self.call_method(
+            &format!("{}.setPurpose", method_prefix(kind)),
+            vec![("channel", channel_id.as_str()), ("purpose", &purpose)],
+        );
+        self.results
+            .send(TaskResult::SetPurpose(channel_id, response))
+            .ok();
+    }
+
+    fn mark_channel(&mut self, channel_id: ChannelID, kind: ChannelKind, message_id: MessageID) {
+        let channel = channel_id.as_str();
+        let ts = message_id.as_str();
+
+        let response = match kind {
+            ChannelKind::Public => slack::api::channels::mark(
+                &self.client,
+                &self.slack_api_key,
+                &slack::api::channels::MarkRequest { channel, ts },
+            )
+                .map(|_| ())
+                .map_err(Error::from),
+            ChannelKind::Private => slack::api::groups::mark(
+                &self.client,
+                &self.slack_api_key,
+                &slack::api::groups::MarkRequest { channel, ts },
+            )
+                .map(|_| ())
+                .map_err(Error::from),
+            ChannelKind::DirectMessage => slack::api::im::mark(
+                &self.client,
+                &self.slack_api_key,
+                &slack::api::im::MarkRequest { channel, ts },
+            )
+                .map(|_| ())
+                .map_err(Error::from),
+            ChannelKind::GroupDirectMessage => slack::api::mpim::mark(
+                &self.client,
+                &self.slack_api_key,
+                &slack::api::mpim::MarkRequest { channel, ts },
+            )
+                .map(|_| ())
+                .map_err(Error::from),
+        };
+        self.results
+            .send(TaskResult::MarkChannel(channel_id, response))
+            .ok();
+    }
+
+    fn add_reaction(&mut self, channel_id: ChannelID, message_id: MessageID, name: String) {
+        let response = slack::api::reactions::add(
+            &self.client,
+            &self.slack_api_key,
+            &slack::api::reactions::AddRequest {
+                name: &name,
+                channel: Some(channel_id.as_str()),
+                timestamp: Some(message_id.as_str()),
+
+                ..Default::default()
+            },
+        );
+        self.results
+            .send(TaskResult::AddReaction(
+                channel_id, message_id, name, response,
+            ))
+            .ok();
+    }
+
+    fn remove_reaction(&mut self, channel_id: ChannelID, message_id: MessageID, name: String) {
+        let response = slack::api::reactions::remove(
+            &self.client,
+            &self.slack_api_key,
+            &slack::api::reactions::RemoveRequest {
+                name: &name,
+                channel: Some(channel_id.as_str()),
+                timestamp: Some(message_id.as_str()),
+
+                ..Default::default()
+            },
+        );
+        self.results
+            .send(TaskResult::RemoveReaction(
+                channel_id, message_id, name, response,
+            ))
+            .ok();
+    }
+
+    fn load_stars(&mut self) {
+        let response = slack::api::stars::list(
+            &self.client,
+            &self.slack_api_key,
+            &slack::api::stars::ListRequest {
+                // Messages and files are starred too, so ask for more than the default page.
+                count: Some(1000),
+
+                ..Default::default()
+            },
+        );
+        self.results.send(TaskResult::Stars(response)).ok();
+    }
+
+    fn star_channel(&mut self, channel_id: ChannelID) {
+        let response = slack::api::stars::add(
+            &self.client,
+            &self.slack_api_key,
+            &slack::api::stars::AddRequest {
+                channel: Some(channel_id.as_str()),
+
+                ..Default::default()
+            },
+        );
+        self.results
+            .send(TaskResult::StarChannel(channel_id, response))
+            .ok();
+    }
+
+    fn unstar_channel(&mut self, channel_id: ChannelID) {
+        let response = slack::api::stars::remove(
+            &self.client,
+            &self.slack_api_key,
+            &slack::api::stars::RemoveRequest {
+                channel: Some(channel_id.as_str()),
+
+                ..Default::default()
+            },
+        );
+        self.results
+            .send(TaskResult::UnstarChannel(channel_id, response))
             .ok();
     }
 }
+
+/// Every kind of channel has its own set of Web API methods.
+fn method_prefix(kind: ChannelKind) -> &'static str {
+    match kind {
+        ChannelKind::Public => "channels",
+        ChannelKind::Private => "groups",
+        ChannelKind::DirectMessage => "im",
+        ChannelKind::GroupDirectMessage => "mpim",
+    }
+}
+
+fn messages_in(response: &Value) -> Vec<Value> {
+    response["messages"].as_array().cloned().unwrap_or_default()
+}
@@ -1,8 +1,11 @@
 extern crate chrono;
 extern crate dotenv;
+extern crate serde_json;
 extern crate slack;
 extern crate termion;
 extern crate tui;
+extern crate tungstenite;
+extern crate url;
 
 #[macro_use]
 extern crate failure;
@@ -57,6 +60,8 @@ fn main() {
 }
 
 fn main_with_result(terminal: &mut TerminalBackend) -> Result<(), Error> {
+    let keymap = components::Keymap::load().context("Could not load key bindings")?;
+
     let slack_api_token = ::std::env::var("SLACK_API_TOKEN")
         .context("Could not read SLACK_API_TOKEN environment variable")?;
 
@@ -69,11 +74,12 @@ fn main_with_result(terminal: &mut TerminalBackend) -> Result<(), Error> {
 
     // Start to pre-load some history to get time-to-initial-render down.
     app.async_load_channel_history(&selected_channel_id)?;
+    app.loader_mut().load_stars()?;
 
     // Let app take over terminal and start main event loops.
     terminal.clear()?;
     terminal.hide_cursor()?;
-    let result = components::event_loop::run(&mut app, rtm, terminal);
+    let result = components::event_loop::run(&mut app, rtm, terminal, keymap);
 
     terminal.show_cursor().ok();
     terminal.clear().ok();
@@ -1,6 +1,8 @@
 use chrono::prelude::*;
 use failure::{Error, Fail};
 use std::cell::{Cell, Ref, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::ops::Bound;
 
 use models::*;
 
@@ -11,14 +13,33 @@ pub struct AppState {
     pub chat_canvas: RefCell<Option<Canvas>>,
     pub history_scroll: usize,
     pub last_chat_height: Cell<u16>,
+    /// Row of the "New messages" divider in the rendered chat canvas, if it has one.
+    pub new_messages_row: Cell<Option<u16>>,
+    /// Set when the history should be scrolled to the divider once it has loaded.
+    pub scroll_to_new_messages: bool,
+    /// Rows of each message in the rendered chat canvas, top to bottom.
+    pub message_rows: RefCell<Vec<MessageRows>>,
+    /// The message in the selected channel that actions like opening threads apply to.
+    pub selected_message: Option<MessageID>,
+    /// Set when the history should be scrolled to show the selected message once rendered.
+    pub scroll_to_selected_message: bool,
 
     pub selected_channel_id: ChannelID,
     pub channels: ChannelList,
+    pub unreads: HashMap<ChannelID, UnreadCount>,
+    /// The newest message that Slack knows the user has read, per channel.
+    pub last_read: HashMap<ChannelID, MessageID>,
+    /// Messages after this one in the selected channel were unread when the channel was opened.
+    pub new_messages_after: Option<MessageID>,
+    /// The thread in the selected channel that is open in the thread pane.
+    pub selected_thread: Option<MessageID>,
 
     pub is_loading_more_messages: bool,
+    pub failed_history_channels: HashSet<ChannelID>,
     pub messages: MessageBuffer,
 
     pub team_name: String,
+    pub current_user_id: UserID,
     pub users: UserList,
 }
 
@@ -31,14 +52,25 @@ impl AppState {
             chat_canvas: RefCell::new(None),
             history_scroll: 0,
             last_chat_height: Cell::new(0),
+            new_messages_row: Cell::new(None),
+            scroll_to_new_messages: false,
+            message_rows: RefCell::new(Vec::new()),
+            selected_message: None,
+            scroll_to_selected_message: false,
 
             selected_channel_id: ChannelID::from("C0"),
             channels: ChannelList::default(),
+            unreads: HashMap::new(),
+            last_read: HashMap::new(),
+            new_messages_after: None,
+            selected_thread: None,
 
             is_loading_more_messages: false,
+            failed_history_channels: HashSet::new(),
             messages: MessageBuffer::default(),
 
             team_name: String::from("Fake Team"),
+            current_user_id: UserID::from("U0"),
             users: UserList::default(),
         }
     }
@@ -60,6 +92,15 @@ impl AppState {
         Ref::map(self.chat_canvas.borrow(), |option| option.as_ref().unwrap())
     }
 
+    /// Renders the open thread, if any. Threads are short, so they are not cached like the
+    /// channel history.
+    pub fn render_thread_canvas(&self, width: u16) -> Option<Canvas> {
+        self.selected_thread.as_ref().map(|thread_id| {
+            self.messages
+                .render_thread_as_canvas(self, &self.selected_channel_id, thread_id, width)
+        })
+    }
+
     pub fn selected_channel(&self) -> Option<&Channel> {
         self.channels.get(&self.selected_channel_id)
     }
@@ -90,6 +131,35 @@ impl AppState {
         self.history_scroll.min(self.max_history_scroll())
     }
 
+    /// Returns true if the rendered history is scrolled within 10% of the top, or if the history
+    /// does not even fill the viewport.
+    ///
+    /// Returns false when the history has not been rendered yet, as the scroll position is not
+    /// known then.
+    pub fn is_near_top_of_history(&self) -> bool {
+        if self.chat_canvas.borrow().is_none() {
+            return false;
+        }
+
+        let max_scroll = self.max_history_scroll();
+        self.current_history_scroll() + max_scroll / 10 >= max_scroll
+    }
+
+    /// Returns true if older history should be requested for the selected channel.
+    ///
+    /// Channels that have reached their start, or that failed to load since they were last
+    /// selected, will not request more.
+    pub fn should_load_older_history(&self) -> bool {
+        !self.is_loading_more_messages
+            && !self.messages.has_channel_start(&self.selected_channel_id)
+            && !self.failed_history_channels.contains(&self.selected_channel_id)
+            && self.is_near_top_of_history()
+    }
+
+    pub fn mark_history_load_failed(&mut self, channel_id: ChannelID) {
+        self.failed_history_channels.insert(channel_id);
+    }
+
     pub fn chat_height(&self) -> u16 {
         self.last_chat_height.get()
     }
@@ -117,12 +187,138 @@ impl AppState {
     }
 
     pub fn select_channel(&mut self, id: ChannelID) -> Result<(), Error> {
+        self.new_messages_after = match self.unreads.remove(&id) {
+            Some(_) => self.last_read.get(&id).cloned(),
+            None => None,
+        };
+        self.scroll_to_new_messages = self.new_messages_after.is_some();
+        self.failed_history_channels.remove(&id);
         self.selected_channel_id = id;
+        self.selected_thread = None;
+        self.selected_message = None;
         self.history_scroll = 0;
         self.clear_chat_canvas_cache();
         Ok(())
     }
 
+    /// Scrolls the "New messages" divider to the top of the viewport, once the history of a newly
+    /// selected channel has been loaded and rendered.
+    pub fn scroll_to_new_messages_if_needed(&mut self) {
+        if !self.scroll_to_new_messages
+            || self.is_loading_more_messages
+            || self.chat_canvas.borrow().is_none()
+        {
+            return;
+        }
+
+        self.scroll_to_new_messages = false;
+        if let Some(row) = self.new_messages_row.get() {
+            // NOTE: Scroll value is distance from bottom, and max scroll shows the top row.
+            self.history_scroll = self.max_history_scroll().saturating_sub(row as usize);
+        }
+    }
+
+    pub fn select_message(&mut self, message_id: Option<MessageID>) {
+        self.scroll_to_selected_message = message_id.is_some();
+        self.selected_message = message_id;
+        self.clear_chat_canvas_cache();
+    }
+
+    /// Moves the selection to the message above the selected one, or selects the newest message
+    /// if none is selected.
+    pub fn select_previous_message(&mut self) {
+        let previous = {
+            let message_rows = self.message_rows.borrow();
+            let index = match self.selected_message_index() {
+                Some(index) => index.saturating_sub(1),
+                None => message_rows.len().saturating_sub(1),
+            };
+            message_rows.get(index).map(|rows| rows.id.clone())
+        };
+
+        if previous.is_some() {
+            self.select_message(previous);
+        }
+    }
+
+    /// Moves the selection to the message below the selected one. Moving past the newest message
+    /// removes the selection and goes back to the bottom of the history.
+    pub fn select_next_message(&mut self) {
+        let index = match self.selected_message_index() {
+            Some(index) => index,
+            None => return,
+        };
+
+        let next = self
+            .message_rows
+            .borrow()
+            .get(index + 1)
+            .map(|rows| rows.id.clone());
+        if next.is_none() {
+            self.history_scroll = 0;
+        }
+        self.select_message(next);
+    }
+
+    /// Returns the selected message, if it is a message from Slack.
+    pub fn selected_standard_message(&self) -> Option<&StandardMessage> {
+        let selected = self.selected_message.as_ref()?;
+        match self.messages.get(&self.selected_channel_id, selected) {
+            Some(&Message::Standard(ref message)) => Some(message),
+            _ => None,
+        }
+    }
+
+    fn selected_message_index(&self) -> Option<usize> {
+        let selected = self.selected_message.as_ref()?;
+        self.message_rows
+            .borrow()
+            .iter()
+            .position(|rows| &rows.id == selected)
+    }
+
+    /// Scrolls the history just enough to show the selected message, once it has been rendered.
+    pub fn scroll_to_selected_message_if_needed(&mut self) {
+        if !self.scroll_to_selected_message {
+            return;
+        }
+        let canvas_height = match *self.chat_canvas.borrow() {
+            Some(ref canvas) => canvas.height() as usize,
+            None => return,
+        };
+
+        self.scroll_to_selected_message = false;
+        let rows = match self.selected_message_index() {
+            Some(index) => self.message_rows.borrow()[index].rows.clone(),
+            None => return,
+        };
+
+        // NOTE: Scroll value is distance from bottom
+        let viewport_height = self.chat_height() as usize;
+        let visible_bottom = canvas_height - self.current_history_scroll();
+        let visible_top = visible_bottom.saturating_sub(viewport_height);
+
+        if rows.end as usize > visible_bottom {
+            self.history_scroll = canvas_height - rows.end as usize;
+        }
+        // Show the start of messages that are taller than the viewport.
+        if (rows.start as usize) < visible_top || rows.len() > viewport_height {
+            self.history_scroll =
+                canvas_height.saturating_sub(rows.start as usize + viewport_height);
+        }
+    }
+
+    pub fn open_thread(&mut self, thread_id: MessageID) {
+        self.selected_thread = Some(thread_id);
+        // The history pane gets narrower.
+        self.clear_chat_canvas_cache();
+    }
+
+    pub fn close_thread(&mut self) {
+        self.selected_thread = None;
+        self.clear_chat_canvas_cache();
+    }
+
     pub fn toggle_loading_state(&mut self) {
         let new_state = !self.is_loading_more_messages;
         self.set_loading_state(new_state);
@@ -138,6 +334,150 @@ impl AppState {
         self.clear_chat_canvas_cache();
     }
 
+    pub fn set_channel_starred(&mut self, channel_id: &ChannelID, is_starred: bool) {
+        if let Some(channel) = self.channels.get_mut(channel_id) {
+            channel.set_is_starred(is_starred);
+        }
+    }
+
+    /// Applies an event received live from Slack. New messages in other channels than the
+    /// selected one are counted as unread.
+    pub fn receive_message_event(&mut self, event: MessageEvent) {
+        if let MessageEvent::New(Message::Standard(ref message)) = event {
+            self.count_unread_message(message);
+        }
+        self.apply_message_event(event);
+    }
+
+    fn count_unread_message(&mut self, message: &StandardMessage) {
+        if message.channel_id == self.selected_channel_id
+            || message.user_id == self.current_user_id
+            || !message.is_shown_in_channel()
+        {
+            return;
+        }
+
+        self.unreads
+            .entry(message.channel_id.clone())
+            .or_insert_with(UnreadCount::default)
+            .add_message(message, &self.current_user_id);
+    }
+
+    /// Returns the newest message in the selected channel that should be marked as read, if the
+    /// user is scrolled to the bottom of the history and it has not been marked already.
+    pub fn message_to_mark(&self) -> Option<MessageID> {
+        if self.chat_canvas.borrow().is_none() || self.current_history_scroll() > 0 {
+            return None;
+        }
+
+        let newest_id = &self
+            .messages
+            .newest_channel_message(&self.selected_channel_id)?
+            .message_id;
+
+        match self.last_read.get(&self.selected_channel_id) {
+            Some(last_read) if last_read >= newest_id => None,
+            _ => Some(newest_id.clone()),
+        }
+    }
+
+    pub fn set_last_read(&mut self, channel_id: &ChannelID, message_id: MessageID) {
+        self.last_read.insert(channel_id.clone(), message_id);
+    }
+
+    /// Applies a read marker moved on Slack, for example by reading the channel in another client.
+    /// Unread counts are recalculated from the known messages after the marker.
+    pub fn accept_channel_marked(&mut self, channel_id: ChannelID, message_id: MessageID) {
+        let mut unread = UnreadCount::default();
+        if channel_id != self.selected_channel_id {
+            let after_marker = (Bound::Excluded(message_id.clone()), Bound::Unbounded);
+            for message in self.messages.channel_range(&channel_id, after_marker) {
+                match *message {
+                    Message::Standard(ref message)
+                        if message.user_id != self.current_user_id
+                            && message.is_shown_in_channel() =>
+                    {
+                        unread.add_message(message, &self.current_user_id)
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        if unread.messages > 0 {
+            self.unreads.insert(channel_id.clone(), unread);
+        } else {
+            self.unreads.remove(&channel_id);
+        }
+        self.last_read.insert(channel_id, message_id);
+    }
+
+    pub fn apply_message_event(&mut self, event: MessageEvent) {
+        match event {
+            MessageEvent::New(message) => self.add_message(message),
+            MessageEvent::Edited {
+                channel_id,
+                message_id,
+                body,
+            } => {
+                if self.messages.edit_message(&channel_id, &message_id, body) {
+                    self.clear_chat_canvas_cache();
+                }
+            }
+            MessageEvent::Deleted {
+                channel_id,
+                message_id,
+            } => {
+                if self.messages.remove(&channel_id, &message_id).is_some() {
+                    self.clear_chat_canvas_cache();
+                }
+            }
+            MessageEvent::Replied {
+                channel_id,
+                message_id,
+                reply_count,
+            } => {
+                if self
+                    .messages
+                    .set_reply_count(&channel_id, &message_id, reply_count)
+                {
+                    self.clear_chat_canvas_cache();
+                }
+            }
+            MessageEvent::ReactionAdded {
+                channel_id,
+                message_id,
+                name,
+                user_id,
+            } => {
+                if self
+                    .messages
+                    .add_reaction(&channel_id, &message_id, &name, user_id)
+                {
+                    self.clear_chat_canvas_cache();
+                }
+            }
+            MessageEvent::ReactionRemoved {
+                channel_id,
+                message_id,
+                name,
+                user_id,
+            } => {
+                if self
+                    .messages
+                    .remove_reaction(&channel_id, &message_id, &name, &user_id)
+                {
+                    self.clear_chat_canvas_cache();
+                }
+            }
+        }
+    }
+
+    pub fn add_channel_start(&mut self, channel_id: &ChannelID) {
+        self.messages.mark_channel_start(channel_id);
+        self.clear_chat_canvas_cache();
+    }
+
     pub fn add_fake_message(&mut self, msg: Option<&str>) {
         let time = Local::now();
 
@@ -152,23 +492,50 @@ impl AppState {
             message_id: time.into(),
             thread_id: time.into(),
             channel_id: self.selected_channel_id.clone(),
+            is_edited: false,
+            is_broadcast: false,
+            reply_count: 0,
+            reactions: Vec::new(),
         });
         self.clear_chat_canvas_cache();
     }
 
     pub fn add_error_message<E: Fail>(&mut self, error: E) {
-        self.messages.add(messages::ErrorMessage::from_error(
-            &self.selected_channel_id,
-            error,
-        ));
+        let channel_id = self.selected_channel_id.clone();
+        self.add_channel_error_message(&channel_id, error);
+    }
+
+    pub fn add_channel_error_message<E: Fail>(&mut self, channel_id: &ChannelID, error: E) {
+        self.messages
+            .add(messages::ErrorMessage::from_error(channel_id, error));
         self.clear_chat_canvas_cache();
     }
 }
 
+/// Number of messages in a channel that the user has not seen yet.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct UnreadCount {
+    pub messages: usize,
+    /// Unread messages that mention the user.
+    pub mentions: usize,
+}
+
+impl UnreadCount {
+    fn add_message(&mut self, message: &StandardMessage, user_id: &UserID) {
+        self.messages += 1;
+        if message.mentions(user_id) {
+            self.mentions += 1;
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Mode {
     History,
+    Normal,
+    Insert,
     SelectChannel,
+    Command,
 }
 
 impl Default for Mode {
@@ -176,3 +543,362 @@ impl Default for Mode {
         Mode::History
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unread_count(state: &AppState, channel_id: &str) -> UnreadCount {
+        state
+            .unreads
+            .get(&ChannelID::from(channel_id))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn add_message(state: &mut AppState, id: &str, body: &str) {
+        state.add_message(Message::Standard(StandardMessage {
+            user_id: "U1".into(),
+            body: body.into(),
+            message_id: id.into(),
+            thread_id: id.into(),
+            channel_id: "C0".into(),
+            is_edited: false,
+            is_broadcast: false,
+            reply_count: 0,
+            reactions: Vec::new(),
+        }));
+    }
+
+    #[test]
+    fn it_keeps_scroll_anchored_when_older_messages_are_added() {
+        let mut state = AppState::fixture();
+        for i in 0..10 {
+            add_message(&mut state, &format!("14000000{:02}.000000", i + 10), "Newer");
+        }
+        state.rendered_chat_canvas(20, 5);
+        state.scroll_up(7);
+        let top_line_before = state.rendered_chat_canvas(20, 5).height() as usize - 7 - 5;
+
+        add_message(&mut state, "1400000000.000000", "Older");
+        let canvas_height = state.rendered_chat_canvas(20, 5).height() as usize;
+
+        assert_eq!(state.current_history_scroll(), 7);
+        // Three lines were prepended (user, body and spacing), so the viewport moved down by the
+        // same amount when counting from the top.
+        assert_eq!(canvas_height - 7 - 5, top_line_before + 3);
+    }
+
+    #[test]
+    fn it_rerenders_edited_messages() {
+        let mut state = AppState::fixture();
+        add_message(&mut state, "1400000000.000000", "Tpyo");
+        assert!(!state.rendered_chat_canvas(30, 5).render_to_string(None).contains("edited"));
+
+        state.apply_message_event(MessageEvent::Edited {
+            channel_id: "C0".into(),
+            message_id: "1400000000.000000".into(),
+            body: String::from("Typo"),
+        });
+
+        let rendered = state.rendered_chat_canvas(30, 5).render_to_string(None);
+        assert!(rendered.contains("Typo (edited)"));
+        assert!(!rendered.contains("Tpyo"));
+    }
+
+    #[test]
+    fn it_removes_deleted_messages() {
+        let mut state = AppState::fixture();
+        add_message(&mut state, "1400000000.000000", "Keep me");
+        add_message(&mut state, "1400000001.000000", "Delete me");
+        assert!(state.rendered_chat_canvas(30, 5).render_to_string(None).contains("Delete me"));
+
+        state.apply_message_event(MessageEvent::Deleted {
+            channel_id: "C0".into(),
+            message_id: "1400000001.000000".into(),
+        });
+
+        let rendered = state.rendered_chat_canvas(30, 5).render_to_string(None);
+        assert!(rendered.contains("Keep me"));
+        assert!(!rendered.contains("Delete me"));
+    }
+
+    #[test]
+    fn it_ignores_messages_deleted_before_they_were_loaded() {
+        let mut state = AppState::fixture();
+        add_message(&mut state, "1400000002.000000", "Newest");
+
+        // The deletion arrives while an older page of history is loading.
+        state.apply_message_event(MessageEvent::Deleted {
+            channel_id: "C0".into(),
+            message_id: "1400000001.000000".into(),
+        });
+        add_message(&mut state, "1400000000.000000", "Keep me");
+        add_message(&mut state, "1400000001.000000", "Delete me");
+
+        let rendered = state.rendered_chat_canvas(30, 5).render_to_string(None);
+        assert!(rendered.contains("Keep me"));
+        assert!(!rendered.contains("Delete me"));
+    }
+
+    #[test]
+    fn it_wants_older_history_when_near_top() {
+        let mut state = AppState::fixture();
+        for i in 0..10 {
+            add_message(&mut state, &format!("14000000{:02}.000000", i + 10), "Message");
+        }
+
+        // Not rendered yet; scroll position is unknown.
+        assert!(!state.should_load_older_history());
+
+        state.rendered_chat_canvas(20, 5);
+        assert!(!state.should_load_older_history());
+
+        let max_scroll = state.max_history_scroll();
+        state.scroll_up(max_scroll - 1);
+        assert!(state.should_load_older_history());
+
+        state.set_loading_state(true);
+        state.rendered_chat_canvas(20, 5);
+        assert!(!state.should_load_older_history());
+
+        state.set_loading_state(false);
+        state.rendered_chat_canvas(20, 5);
+        state.add_channel_start(&ChannelID::from("C0"));
+        state.rendered_chat_canvas(20, 5);
+        assert!(!state.should_load_older_history());
+    }
+
+    #[test]
+    fn it_retries_failed_history_when_the_channel_is_selected_again() {
+        let mut state = AppState::fixture();
+        for i in 0..10 {
+            add_message(&mut state, &format!("14000000{:02}.000000", i + 10), "Message");
+        }
+        state.rendered_chat_canvas(20, 5);
+        let max_scroll = state.max_history_scroll();
+        state.scroll_up(max_scroll);
+
+        state.mark_history_load_failed(ChannelID::from("C0"));
+        state.rendered_chat_canvas(20, 5);
+        assert!(!state.should_load_older_history());
+
+        state.select_channel(ChannelID::from("C0")).unwrap();
+        state.rendered_chat_canvas(20, 5);
+        state.scroll_up(max_scroll);
+        assert!(state.should_load_older_history());
+    }
+
+    #[test]
+    fn it_stars_and_unstars_known_channels() {
+        let mut state = AppState::fixture();
+        state.channels.add_channel(Channel::fixture("C1", "general"));
+
+        state.set_channel_starred(&ChannelID::from("C1"), true);
+        assert!(state.channels.get(&ChannelID::from("C1")).unwrap().is_starred());
+
+        state.set_channel_starred(&ChannelID::from("C1"), false);
+        assert!(!state.channels.get(&ChannelID::from("C1")).unwrap().is_starred());
+
+        // Stars for channels that are not loaded are ignored.
+        state.set_channel_starred(&ChannelID::from("C2"), true);
+        assert!(state.channels.get(&ChannelID::from("C2")).is_none());
+    }
+
+    fn live_message(channel: &str, user: &str, id: &str, body: &str) -> MessageEvent {
+        MessageEvent::New(Message::Standard(StandardMessage {
+            user_id: user.into(),
+            body: body.into(),
+            message_id: id.into(),
+            thread_id: id.into(),
+            channel_id: channel.into(),
+            is_edited: false,
+            is_broadcast: false,
+            reply_count: 0,
+            reactions: Vec::new(),
+        }))
+    }
+
+    #[test]
+    fn it_counts_unread_messages_in_other_channels() {
+        let mut state = AppState::fixture();
+
+        state.receive_message_event(live_message("C1", "U1", "1.0", "Hello"));
+        state.receive_message_event(live_message("C1", "U1", "2.0", "Hi <@U0>!"));
+        state.receive_message_event(live_message("C2", "U1", "3.0", "<!channel> lunch"));
+
+        assert_eq!(
+            unread_count(&state, "C1"),
+            UnreadCount {
+                messages: 2,
+                mentions: 1,
+            }
+        );
+        assert_eq!(
+            unread_count(&state, "C2"),
+            UnreadCount {
+                messages: 1,
+                mentions: 1,
+            }
+        );
+        assert_eq!(state.messages.channel_iter(&"C1".into()).count(), 2);
+    }
+
+    #[test]
+    fn it_does_not_count_selected_channel_or_own_messages() {
+        let mut state = AppState::fixture();
+
+        state.receive_message_event(live_message("C0", "U1", "1.0", "Hello"));
+        state.receive_message_event(live_message("C1", "U0", "2.0", "Hello"));
+
+        assert_eq!(unread_count(&state, "C0"), UnreadCount::default());
+        assert_eq!(unread_count(&state, "C1"), UnreadCount::default());
+    }
+
+    #[test]
+    fn it_counts_replies_only_on_the_thread() {
+        let mut state = AppState::fixture();
+        state.receive_message_event(live_message("C1", "U1", "1.0", "Lunch?"));
+
+        let mut reply = live_message("C1", "U2", "2.0", "Yes!");
+        if let MessageEvent::New(Message::Standard(ref mut message)) = reply {
+            message.thread_id = "1.0".into();
+        }
+        state.receive_message_event(reply);
+        state.receive_message_event(MessageEvent::Replied {
+            channel_id: "C1".into(),
+            message_id: "1.0".into(),
+            reply_count: 1,
+        });
+
+        assert_eq!(unread_count(&state, "C1").messages, 1);
+        match state.messages.get(&"C1".into(), &"1.0".into()) {
+            Some(&Message::Standard(ref message)) => assert_eq!(message.reply_count, 1),
+            other => panic!("Unexpected message: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn it_clears_unreads_when_viewing_a_channel() {
+        let mut state = AppState::fixture();
+        state.receive_message_event(live_message("C1", "U1", "1.0", "Hello"));
+
+        state.select_channel("C1".into()).unwrap();
+
+        assert_eq!(unread_count(&state, "C1"), UnreadCount::default());
+    }
+
+    #[test]
+    fn it_moves_the_selection_and_keeps_it_visible() {
+        let mut state = AppState::fixture();
+        for i in 0..10 {
+            add_message(&mut state, &format!("14000000{:02}.000000", i + 10), "Hello");
+        }
+        // Every message takes three rows, including the empty row after it.
+        state.rendered_chat_canvas(20, 5);
+
+        state.select_previous_message();
+        assert_eq!(state.selected_message, Some("1400000019.000000".into()));
+
+        for _ in 0..3 {
+            state.select_previous_message();
+        }
+        assert_eq!(state.selected_message, Some("1400000016.000000".into()));
+
+        state.rendered_chat_canvas(20, 5);
+        state.scroll_to_selected_message_if_needed();
+        // The selected message starts at row 18, which is now the top of the viewport.
+        assert_eq!(state.current_history_scroll(), 30 - 18 - 5);
+
+        state.select_next_message();
+        state.rendered_chat_canvas(20, 5);
+        state.scroll_to_selected_message_if_needed();
+        assert_eq!(state.selected_message, Some("1400000017.000000".into()));
+        assert_eq!(state.current_history_scroll(), 30 - 18 - 5);
+
+        for _ in 0..3 {
+            state.select_next_message();
+        }
+        assert_eq!(state.selected_message, None);
+        assert_eq!(state.current_history_scroll(), 0);
+    }
+
+    #[test]
+    fn it_marks_the_newest_message_when_scrolled_to_the_bottom() {
+        let mut state = AppState::fixture();
+        for i in 1..10 {
+            add_message(&mut state, &format!("{}.0", i), "Hello\nWorld");
+        }
+
+        // History has not been rendered yet, so it's not known if the user can see it.
+        assert_eq!(state.message_to_mark(), None);
+
+        state.rendered_chat_canvas(20, 5);
+        assert_eq!(state.message_to_mark(), Some(MessageID::from("9.0")));
+
+        state.set_last_read(&"C0".into(), "9.0".into());
+        assert_eq!(state.message_to_mark(), None);
+
+        add_message(&mut state, "9.5", "Hello");
+        state.rendered_chat_canvas(20, 5);
+        state.scroll_up(3);
+        assert_eq!(state.message_to_mark(), None);
+
+        state.scroll_down(3);
+        assert_eq!(state.message_to_mark(), Some(MessageID::from("9.5")));
+    }
+
+    #[test]
+    fn it_recounts_unreads_when_a_channel_is_marked() {
+        let mut state = AppState::fixture();
+        state.receive_message_event(live_message("C1", "U1", "1.0", "Hello"));
+        state.receive_message_event(live_message("C1", "U1", "2.0", "Hi <@U0>!"));
+        state.receive_message_event(live_message("C1", "U1", "3.0", "Anyone?"));
+
+        state.accept_channel_marked("C1".into(), "1.0".into());
+        assert_eq!(
+            unread_count(&state, "C1"),
+            UnreadCount {
+                messages: 2,
+                mentions: 1,
+            }
+        );
+        assert_eq!(state.last_read.get(&"C1".into()), Some(&MessageID::from("1.0")));
+
+        state.accept_channel_marked("C1".into(), "3.0".into());
+        assert_eq!(unread_count(&state, "C1"), UnreadCount::default());
+    }
+
+    #[test]
+    fn it_scrolls_to_new_messages_when_opening_a_channel() {
+        let mut state = AppState::fixture();
+        for i in 1..10 {
+            add_message(&mut state, &format!("{}.0", i), "Hello\nWorld");
+        }
+        state.unreads.insert("C0".into(), UnreadCount::default());
+        state.set_last_read(&"C0".into(), "3.0".into());
+
+        state.select_channel("C0".into()).unwrap();
+        assert_eq!(state.new_messages_after, Some(MessageID::from("3.0")));
+
+        // Nothing happens until the history has been rendered.
+        state.scroll_to_new_messages_if_needed();
+        assert!(state.scroll_to_new_messages);
+
+        state.rendered_chat_canvas(20, 5);
+        let divider_row = state.new_messages_row.get().expect("Divider was not rendered");
+        state.scroll_to_new_messages_if_needed();
+
+        assert!(!state.scroll_to_new_messages);
+        assert_eq!(
+            state.current_history_scroll(),
+            state.max_history_scroll() - divider_row as usize
+        );
+
+        // Opening a channel without unreads does not show a divider.
+        state.select_channel("C0".into()).unwrap();
+        state.rendered_chat_canvas(20, 5);
+        assert_eq!(state.new_messages_after, None);
+        assert_eq!(state.new_messages_row.get(), None);
+    }
+}
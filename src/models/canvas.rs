@@ -2,7 +2,7 @@ use std::fmt;
 use std::fmt::Debug;
 
 use tui::buffer::{Buffer, Cell};
-use tui::style::Style;
+use tui::style::{Color, Style};
 
 #[derive(Clone)]
 pub struct Canvas {
@@ -45,6 +45,54 @@ impl Canvas {
         }
     }
 
+    /// Adds a character that is two cells wide in the terminal, like most emoji. The second cell
+    /// is left empty so the rest of the line is not pushed to the right.
+    pub fn add_wide_character(&mut self, symbol: &str, style: Style) {
+        if self.line_full {
+            self.line_full = false;
+        } else if self.width - self.total_characters_on_last_line() < 2 {
+            self.complete_line(style);
+        }
+
+        let mut cell = Cell::default();
+        cell.set_symbol(symbol).set_style(style);
+        self.cells.push(cell);
+        self.add_cell(' ', style);
+        self.cells.last_mut().unwrap().set_symbol("");
+        self.line_full = self.total_characters_on_last_line() == 0;
+    }
+
+    /// Adds every line of another canvas on lines of its own, each starting with `prefix`. The
+    /// other canvas should be narrower by the width of the prefix; anything further is cut off.
+    pub fn add_prefixed_lines(&mut self, prefix: &str, prefix_style: Style, lines: &Canvas) {
+        if lines.cells.is_empty() {
+            return;
+        }
+        if self.total_characters_on_last_line() > 0 {
+            self.complete_line(Style::default());
+        }
+
+        for line in lines.cells.chunks(lines.width as usize) {
+            self.line_full = false;
+            self.add_string_truncated(prefix, prefix_style);
+            let room = (self.width - self.total_characters_on_last_line()) as usize;
+            self.cells.extend(line.iter().take(room).cloned());
+            if self.total_characters_on_last_line() > 0 {
+                self.complete_line(Style::default());
+            }
+        }
+
+        // Like after wrapping, a newline right after the last line should not add an empty one.
+        self.line_full = true;
+    }
+
+    /// Changes the background of everything written so far, for example to highlight it.
+    pub fn set_background(&mut self, color: Color) {
+        for cell in &mut self.cells {
+            cell.style = cell.style.bg(color);
+        }
+    }
+
     pub fn render_viewport(&self, viewport_options: ViewportOptions) -> Buffer {
         use tui::layout::Rect;
         let rect = Rect::new(
@@ -74,19 +122,14 @@ impl Canvas {
         let mut total_chars = 0;
         let width = self.width as usize;
 
-        for (i, chr) in self
-            .cells
-            .iter()
-            .flat_map(|c| c.symbol.chars().next())
-            .enumerate()
-        {
+        for (i, symbol) in self.cells.iter().map(|c| c.symbol.as_str()).enumerate() {
             if i > 0 && i % width == 0 {
                 if let &Some(marker) = &eol_marker {
                     s.push_str(marker);
                 }
                 s.push('\n');
             }
-            s.push(chr);
+            s.push_str(symbol);
             total_chars += 1;
         }
 
@@ -232,7 +275,6 @@ impl ViewportOptions {
 mod tests {
     use super::*;
     use tui::layout::Rect;
-    use tui::style::Color;
 
     fn cell(chr: char, style: Style) -> Cell {
         let mut cell = Cell::default();
@@ -385,6 +427,58 @@ mod tests {
         assert_eq!(top.height(), 2);
     }
 
+    #[test]
+    fn it_changes_background_of_written_cells() {
+        let red = Style::default().fg(Color::Red);
+
+        let mut canvas = Canvas::new(3);
+        canvas.add_string_truncated("Hi\n", red);
+        canvas.set_background(Color::DarkGray);
+
+        assert_eq!(canvas.get_pos(0, 0), Some(&cell('H', red.bg(Color::DarkGray))));
+        assert_eq!(canvas.get_pos(2, 0), Some(&cell(' ', red.bg(Color::DarkGray))));
+    }
+
+    #[test]
+    fn it_adds_prefixed_lines() {
+        let style = Style::default();
+        let mut quote = Canvas::new(4);
+        quote.add_string_wrapped("abcdef\ng", style);
+
+        let mut canvas = Canvas::new(6);
+        canvas.add_string_wrapped("x", style);
+        canvas.add_prefixed_lines("> ", style, &quote);
+        canvas.add_string_wrapped("\ny", style);
+
+        assert_eq!(
+            &canvas.render_to_string(Some("|")),
+            "x     |
+> abcd|
+> ef  |
+> g   |
+y     |"
+        );
+    }
+
+    #[test]
+    fn it_adds_wide_characters() {
+        let style = Style::default();
+
+        let mut canvas = Canvas::new(4);
+        canvas.add_string_wrapped("ab", style);
+        canvas.add_wide_character("👍", style);
+        canvas.add_string_wrapped("c", style);
+        canvas.add_wide_character("🎉", style);
+        // Does not fit on the line anymore.
+        canvas.add_wide_character("🎉", style);
+        canvas.add_string_wrapped("\n", style);
+
+        // The second cell of every wide character has an empty symbol.
+        assert_eq!(canvas.height(), 3);
+        assert_eq!(canvas.get_pos(3, 0).unwrap().symbol, "");
+        assert_eq!(&canvas.render_to_string(Some("|")), "ab👍|\nc🎉 |\n🎉  |");
+    }
+
     #[test]
     #[should_panic]
     fn it_panics_when_adding_different_widths() {
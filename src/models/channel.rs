@@ -1,23 +1,38 @@
 extern crate slack;
-use slack::api::{Channel as SlackChannel, ChannelTopic};
+use slack::api::{
+    Channel as SlackChannel, ChannelTopic, Group as SlackGroup, GroupTopic, Im as SlackIm,
+    Mpim as SlackMpim,
+};
 
 use std::collections::BTreeMap;
 use std::hash::{Hash, Hasher};
 use std::iter::FromIterator;
 
+use models::{UserID, UserList};
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct ChannelID(String);
 
 #[derive(Debug, Clone)]
 pub struct Channel {
     id: ChannelID,
+    kind: ChannelKind,
     name: String,
     is_member: bool,
     is_starred: bool,
-    has_unreads: bool,
     topic_text: Option<String>,
 }
 
+/// The different kinds of conversations, which use different Slack API endpoints.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelKind {
+    Public,
+    /// Private channels, called groups in the Slack API.
+    Private,
+    DirectMessage,
+    GroupDirectMessage,
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct ChannelList {
     channels: BTreeMap<ChannelID, Channel>,
@@ -44,34 +59,145 @@ impl Channel {
 
         Some(Channel {
             id,
+            kind: ChannelKind::Public,
             name,
             topic_text,
-            is_starred: false, // TODO. Needs to be read using Slack API stars.list
-            has_unreads: channel.unread_count.unwrap_or(0) > 0,
+            is_starred: false, // Loaded later using stars.list
             is_member: channel.is_member.unwrap_or(false),
         })
     }
 
+    pub fn from_slack_group(group: &SlackGroup) -> Option<Self> {
+        let id = match group.id {
+            Some(ref id) => ChannelID::from(id),
+            None => return None,
+        };
+
+        let name = match group.name {
+            Some(ref name) => name.clone(),
+            None => return None,
+        };
+
+        let topic_text = match group.topic {
+            Some(GroupTopic { ref value, .. }) => value.clone(),
+            None => None,
+        };
+
+        Some(Channel {
+            id,
+            kind: ChannelKind::Private,
+            name,
+            topic_text,
+            is_starred: false,
+            // Groups are only visible to their members.
+            is_member: true,
+        })
+    }
+
+    /// Creates a direct message conversation, named after the other user.
+    pub fn from_slack_im(im: &SlackIm, users: &UserList) -> Option<Self> {
+        let id = match im.id {
+            Some(ref id) => ChannelID::from(id),
+            None => return None,
+        };
+
+        let user_id = match im.user {
+            Some(ref user_id) => UserID::from(user_id),
+            None => return None,
+        };
+
+        Some(Channel {
+            id,
+            kind: ChannelKind::DirectMessage,
+            name: user_name(users, &user_id),
+            topic_text: None,
+            is_starred: false,
+            is_member: true,
+        })
+    }
+
+    /// Creates a group direct message conversation, named after the other participants.
+    pub fn from_slack_mpim(
+        mpim: &SlackMpim,
+        users: &UserList,
+        current_user_id: &UserID,
+    ) -> Option<Self> {
+        let id = match mpim.id {
+            Some(ref id) => ChannelID::from(id),
+            None => return None,
+        };
+
+        let mut names: Vec<String> = mpim
+            .members
+            .iter()
+            .flat_map(|members| members.iter())
+            .map(UserID::from)
+            .filter(|user_id| user_id != current_user_id)
+            .map(|user_id| user_name(users, &user_id))
+            .collect();
+        names.sort();
+
+        Some(Channel {
+            id,
+            kind: ChannelKind::GroupDirectMessage,
+            name: names.join(", "),
+            topic_text: None,
+            is_starred: false,
+            is_member: true,
+        })
+    }
+
     #[cfg(test)]
     pub fn fixture(id: &str, name: &str) -> Channel {
         Channel {
             id: ChannelID::from(id),
+            kind: ChannelKind::Public,
             name: String::from(name),
             is_member: false,
             is_starred: false,
-            has_unreads: false,
             topic_text: None,
         }
     }
 
+    #[cfg(test)]
+    pub fn private_fixture(id: &str, name: &str) -> Channel {
+        Channel {
+            kind: ChannelKind::Private,
+            is_member: true,
+            ..Channel::fixture(id, name)
+        }
+    }
+
+    #[cfg(test)]
+    pub fn direct_message_fixture(id: &str, name: &str) -> Channel {
+        Channel {
+            kind: ChannelKind::DirectMessage,
+            is_member: true,
+            ..Channel::fixture(id, name)
+        }
+    }
+
     pub fn id(&self) -> &ChannelID {
         &self.id
     }
 
+    pub fn kind(&self) -> ChannelKind {
+        self.kind
+    }
+
     pub fn name(&self) -> &str {
         &self.name
     }
 
+    /// Returns the character shown before the name of the channel.
+    pub fn sigil(&self) -> char {
+        if self.kind.is_direct_message() {
+            '@'
+        } else {
+            '#'
+        }
+    }
+
     pub fn topic_text(&self) -> Option<&str> {
         self.topic_text.as_ref().map(String::as_ref)
     }
@@ -80,14 +206,36 @@ impl Channel {
         self.is_member
     }
 
+    pub fn set_is_member(&mut self, is_member: bool) {
+        self.is_member = is_member;
+    }
+
+    pub fn set_topic_text(&mut self, topic_text: Option<String>) {
+        self.topic_text = topic_text;
+    }
+
     pub fn is_starred(&self) -> bool {
-        // TODO: This is just a quick ugly hack to test the "starred" feature
-        self.is_starred || self.name == "team-core" || self.name == "development"
+        self.is_starred
     }
 
-    #[cfg(test)]
-    pub fn has_unreads(&self) -> bool {
-        self.has_unreads
+    pub fn set_is_starred(&mut self, is_starred: bool) {
+        self.is_starred = is_starred;
+    }
+}
+
+fn user_name(users: &UserList, user_id: &UserID) -> String {
+    users
+        .get(user_id)
+        .map(|user| user.display_name().to_owned())
+        .unwrap_or_else(|| user_id.as_str().to_owned())
+}
+
+impl ChannelKind {
+    pub fn is_direct_message(self) -> bool {
+        match self {
+            ChannelKind::DirectMessage | ChannelKind::GroupDirectMessage => true,
+            ChannelKind::Public | ChannelKind::Private => false,
+        }
     }
 }
 
@@ -98,7 +246,6 @@ impl ChannelList {
         }
     }
 
-    #[cfg(test)]
     pub fn add_channel(&mut self, channel: Channel) {
         self.channels.insert(channel.id().clone(), channel);
     }
@@ -110,6 +257,17 @@ impl ChannelList {
     pub fn get(&self, id: &ChannelID) -> Option<&Channel> {
         self.channels.get(id)
     }
+
+    pub fn get_mut(&mut self, id: &ChannelID) -> Option<&mut Channel> {
+        self.channels.get_mut(id)
+    }
+
+    /// Finds a channel by name, ignoring case.
+    pub fn find_by_name(&self, name: &str) -> Option<&Channel> {
+        self.channels
+            .values()
+            .find(|channel| channel.name.eq_ignore_ascii_case(name))
+    }
 }
 
 impl ChannelID {
@@ -157,3 +315,61 @@ impl FromIterator<Channel> for ChannelList {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use models::User;
+    use serde_json;
+
+    fn users() -> UserList {
+        let mut users = UserList::new();
+        users.add_user(User::fixture("U1", "me"));
+        users.add_user(User::fixture("U2", "carol"));
+        users.add_user(User::fixture("U3", "bob"));
+        users
+    }
+
+    #[test]
+    fn it_creates_private_channels_from_groups() {
+        let group: SlackGroup = serde_json::from_str(
+            r#"{"id": "G1", "name": "secret", "topic": {"value": "Shh"}, "is_mpim": false}"#,
+        )
+        .unwrap();
+
+        let channel = Channel::from_slack_group(&group).unwrap();
+        assert_eq!(channel.id(), &ChannelID::from("G1"));
+        assert_eq!(channel.kind(), ChannelKind::Private);
+        assert_eq!(channel.name(), "secret");
+        assert_eq!(channel.topic_text(), Some("Shh"));
+        assert!(channel.is_member());
+    }
+
+    #[test]
+    fn it_names_direct_messages_after_the_other_user() {
+        let im: SlackIm = serde_json::from_str(r#"{"id": "D1", "user": "U2"}"#).unwrap();
+
+        let channel = Channel::from_slack_im(&im, &users()).unwrap();
+        assert_eq!(channel.id(), &ChannelID::from("D1"));
+        assert_eq!(channel.kind(), ChannelKind::DirectMessage);
+        assert_eq!(channel.name(), "carol");
+        assert!(channel.is_member());
+
+        let im: SlackIm = serde_json::from_str(r#"{"id": "D1"}"#).unwrap();
+        assert!(Channel::from_slack_im(&im, &users()).is_none());
+    }
+
+    #[test]
+    fn it_names_group_direct_messages_after_the_other_users_in_order() {
+        let mpim: SlackMpim = serde_json::from_str(
+            r#"{"id": "G2", "name": "mpdm-me--carol--bob-1", "members": ["U2", "U1", "U3"]}"#,
+        )
+        .unwrap();
+
+        let channel = Channel::from_slack_mpim(&mpim, &users(), &UserID::from("U1")).unwrap();
+        assert_eq!(channel.id(), &ChannelID::from("G2"));
+        assert_eq!(channel.kind(), ChannelKind::GroupDirectMessage);
+        assert_eq!(channel.name(), "bob, carol");
+        assert!(channel.is_member());
+    }
+}
@@ -0,0 +1,74 @@
+/// Commonly used emoji shortcodes and the characters they represent.
+///
+/// Slack supports many more, including custom ones per team. Shortcodes not in this table are
+/// shown as `:name:`.
+const EMOJI: &[(&str, &str)] = &[
+    ("+1", "👍"),
+    ("-1", "👎"),
+    ("100", "💯"),
+    ("angry", "😠"),
+    ("bug", "🐛"),
+    ("clap", "👏"),
+    ("confused", "😕"),
+    ("cry", "😢"),
+    ("eyes", "👀"),
+    ("fire", "🔥"),
+    ("grin", "😁"),
+    ("grinning", "😀"),
+    ("heart", "❤"),
+    ("heavy_check_mark", "✔"),
+    ("hugging_face", "🤗"),
+    ("joy", "😂"),
+    ("laughing", "😆"),
+    ("muscle", "💪"),
+    ("ok_hand", "👌"),
+    ("pray", "🙏"),
+    ("raised_hands", "🙌"),
+    ("rocket", "🚀"),
+    ("see_no_evil", "🙈"),
+    ("slightly_smiling_face", "🙂"),
+    ("smile", "😄"),
+    ("smiley", "😃"),
+    ("sob", "😭"),
+    ("sunglasses", "😎"),
+    ("sweat_smile", "😅"),
+    ("tada", "🎉"),
+    ("thinking_face", "🤔"),
+    ("thumbsdown", "👎"),
+    ("thumbsup", "👍"),
+    ("wave", "👋"),
+    ("white_check_mark", "✅"),
+    ("wink", "😉"),
+    ("x", "❌"),
+];
+
+pub fn names() -> impl Iterator<Item = &'static str> {
+    EMOJI.iter().map(|&(name, _)| name)
+}
+
+pub fn character_for(name: &str) -> Option<&'static str> {
+    EMOJI
+        .iter()
+        .find(|&&(emoji_name, _)| emoji_name == name)
+        .map(|&(_, character)| character)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_is_sorted_by_name() {
+        let names: Vec<&str> = names().collect();
+        let mut sorted_names = names.clone();
+        sorted_names.sort();
+
+        assert_eq!(names, sorted_names);
+    }
+
+    #[test]
+    fn it_looks_up_characters() {
+        assert_eq!(character_for("tada"), Some("🎉"));
+        assert_eq!(character_for("party_parrot"), None);
+    }
+}
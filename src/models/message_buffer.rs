@@ -1,38 +1,271 @@
-use std::collections::BTreeMap;
+use std::collections::btree_map;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::{Range, RangeBounds};
 
 use models::messages::*;
-use models::{AppState, Canvas, ChannelID};
+use models::{AppState, Canvas, ChannelID, UserID};
 
+/// Stores history entries for every channel, each channel ordered by `MessageID`.
 #[derive(Debug, Default)]
 pub struct MessageBuffer {
-    messages: BTreeMap<MessageID, Message>,
+    channels: BTreeMap<ChannelID, BTreeMap<MessageID, Message>>,
+    /// Messages that have been deleted, so they are not added again by a history page that was
+    /// loading while they were deleted.
+    deleted: BTreeMap<ChannelID, BTreeSet<MessageID>>,
+}
+
+/// The rows of the rendered history that a message occupies.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageRows {
+    pub id: MessageID,
+    pub rows: Range<u16>,
+}
+
+/// Iterates over messages in a single channel, oldest first.
+pub struct ChannelIter<'a> {
+    range: Option<btree_map::Range<'a, MessageID, Message>>,
 }
 
 impl MessageBuffer {
     pub fn new() -> Self {
         MessageBuffer {
-            messages: BTreeMap::new(),
+            channels: BTreeMap::new(),
+            deleted: BTreeMap::new(),
         }
     }
 
     pub fn add<E: HistoryEntry>(&mut self, entry: E) {
         let message = entry.into_message();
-        self.messages.insert(message.id().clone(), message);
+        if self.is_deleted(message.channel_id(), message.id()) {
+            return;
+        }
+
+        self.channels
+            .entry(message.channel_id().clone())
+            .or_insert_with(BTreeMap::new)
+            .insert(message.id().clone(), message);
+    }
+
+    /// Replaces the body of a message and marks it as edited. Returns false if no such message
+    /// could be edited.
+    pub fn edit_message(&mut self, channel_id: &ChannelID, id: &MessageID, body: String) -> bool {
+        match self.get_mut(channel_id, id) {
+            Some(&mut Message::Standard(ref mut message)) => {
+                message.body = body;
+                message.is_edited = true;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Updates the number of replies in the thread of a message. Returns false if the message is
+    /// not known.
+    pub fn set_reply_count(
+        &mut self,
+        channel_id: &ChannelID,
+        id: &MessageID,
+        reply_count: usize,
+    ) -> bool {
+        match self.get_mut(channel_id, id) {
+            Some(&mut Message::Standard(ref mut message)) => {
+                message.reply_count = reply_count;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Adds a reaction from a user to a message. Returns false if the message is not known.
+    pub fn add_reaction(
+        &mut self,
+        channel_id: &ChannelID,
+        id: &MessageID,
+        name: &str,
+        user_id: UserID,
+    ) -> bool {
+        match self.get_mut(channel_id, id) {
+            Some(&mut Message::Standard(ref mut message)) => {
+                message.add_reaction(name, user_id);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Removes a reaction of a user from a message. Returns false if the message is not known.
+    pub fn remove_reaction(
+        &mut self,
+        channel_id: &ChannelID,
+        id: &MessageID,
+        name: &str,
+        user_id: &UserID,
+    ) -> bool {
+        match self.get_mut(channel_id, id) {
+            Some(&mut Message::Standard(ref mut message)) => {
+                message.remove_reaction(name, user_id);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Removes a message, and makes sure it will not be added again. The message does not have to
+    /// be loaded yet.
+    pub fn remove(&mut self, channel_id: &ChannelID, id: &MessageID) -> Option<Message> {
+        self.deleted
+            .entry(channel_id.clone())
+            .or_insert_with(BTreeSet::new)
+            .insert(id.clone());
+        self.channels
+            .get_mut(channel_id)
+            .and_then(|messages| messages.remove(id))
+    }
+
+    fn is_deleted(&self, channel_id: &ChannelID, id: &MessageID) -> bool {
+        self.deleted
+            .get(channel_id)
+            .map(|ids| ids.contains(id))
+            .unwrap_or(false)
+    }
+
+    pub fn mark_channel_start(&mut self, channel_id: &ChannelID) {
+        self.add(ChannelStartMessage::new(channel_id));
+    }
+
+    pub fn has_channel_start(&self, channel_id: &ChannelID) -> bool {
+        let start_id = ChannelStartMessage::id_for_channel(channel_id);
+        self.channels
+            .get(channel_id)
+            .map(|messages| messages.contains_key(&start_id))
+            .unwrap_or(false)
+    }
+
+    pub fn get(&self, channel_id: &ChannelID, id: &MessageID) -> Option<&Message> {
+        self.channels.get(channel_id).and_then(|messages| messages.get(id))
+    }
+
+    fn get_mut(&mut self, channel_id: &ChannelID, id: &MessageID) -> Option<&mut Message> {
+        self.channels
+            .get_mut(channel_id)
+            .and_then(|messages| messages.get_mut(id))
+    }
+
+    /// Returns the oldest message in the channel, ignoring the ChannelStart marker.
+    pub fn oldest_message(&self, channel_id: &ChannelID) -> Option<&Message> {
+        // ChannelStart always sorts first, so this looks at most at two entries.
+        self.channel_iter(channel_id).find(|m| match **m {
+            Message::ChannelStart(_) => false,
+            _ => true,
+        })
+    }
+
+    /// Returns the newest message from Slack that is shown in the history of the channel. Errors
+    /// and markers only exist in this client, and thread replies are only shown in their thread.
+    pub fn newest_channel_message(&self, channel_id: &ChannelID) -> Option<&StandardMessage> {
+        self.channel_iter(channel_id)
+            .rev()
+            .filter_map(|message| match *message {
+                Message::Standard(ref message) if message.is_shown_in_channel() => Some(message),
+                _ => None,
+            })
+            .next()
+    }
+
+    pub fn oldest_message_id(&self, channel_id: &ChannelID) -> Option<&MessageID> {
+        self.oldest_message(channel_id).map(|m| m.id())
+    }
+
+    pub fn channel_iter(&self, channel_id: &ChannelID) -> ChannelIter {
+        self.channel_range(channel_id, ..)
+    }
+
+    pub fn channel_range<R>(&self, channel_id: &ChannelID, range: R) -> ChannelIter
+    where
+        R: RangeBounds<MessageID>,
+    {
+        ChannelIter {
+            range: self
+                .channels
+                .get(channel_id)
+                .map(|messages| messages.range(range)),
+        }
     }
 
     pub fn render_as_canvas(&self, state: &AppState, width: u16) -> Canvas {
-        use tui::style::Style;
+        use tui::style::{Color, Style};
 
         let mut canvas = Canvas::new(width);
         if state.is_loading_more_messages {
             canvas += LoadingMessage::new().render_as_canvas(state, width);
         }
 
-        for (_id, message) in self
-            .messages
-            .iter()
-            .filter(|&(_, m)| m.channel_id() == state.selected_channel_id())
-        {
+        // The divider is only shown when the last read message is loaded too, as it would
+        // otherwise end up above the oldest loaded message.
+        let mut has_read_messages = false;
+        let mut divider_row = None;
+        let mut message_rows = Vec::new();
+
+        for message in self.channel_iter(state.selected_channel_id()) {
+            // Replies are only shown in the thread pane, unless they were sent to the channel too.
+            if let Message::Standard(ref message) = *message {
+                if !message.is_shown_in_channel() {
+                    continue;
+                }
+            }
+
+            if let Some(ref last_read) = state.new_messages_after {
+                if message.id() <= last_read {
+                    has_read_messages = true;
+                } else if has_read_messages && divider_row.is_none() {
+                    divider_row = Some(canvas.height());
+                    canvas += NewMessagesDivider::new().render_as_canvas(state, width);
+                }
+            }
+
+            let top = canvas.height();
+            let mut message_canvas = message.render_as_canvas(state, width);
+            if state.selected_message.as_ref() == Some(message.id()) {
+                message_canvas.set_background(Color::DarkGray);
+            }
+            canvas += message_canvas;
+            message_rows.push(MessageRows {
+                id: message.id().clone(),
+                rows: top..canvas.height(),
+            });
+            canvas.add_string_truncated("\n", Style::default());
+        }
+
+        state.new_messages_row.set(divider_row);
+        state.message_rows.replace(message_rows);
+        canvas
+    }
+
+    /// Returns the parent message and the known replies of a thread, oldest first.
+    pub fn thread_iter<'a>(
+        &'a self,
+        channel_id: &ChannelID,
+        thread_id: &'a MessageID,
+    ) -> impl Iterator<Item = &'a StandardMessage> {
+        // Replies are always newer than the message that started the thread.
+        self.channel_range(channel_id, thread_id.clone()..)
+            .filter_map(move |message| match *message {
+                Message::Standard(ref message) if &message.thread_id == thread_id => Some(message),
+                _ => None,
+            })
+    }
+
+    pub fn render_thread_as_canvas(
+        &self,
+        state: &AppState,
+        channel_id: &ChannelID,
+        thread_id: &MessageID,
+        width: u16,
+    ) -> Canvas {
+        use tui::style::Style;
+
+        let mut canvas = Canvas::new(width);
+        for message in self.thread_iter(channel_id, thread_id) {
             canvas += message.render_as_canvas(state, width);
             canvas.add_string_truncated("\n", Style::default());
         }
@@ -40,6 +273,26 @@ impl MessageBuffer {
     }
 }
 
+impl<'a> Iterator for ChannelIter<'a> {
+    type Item = &'a Message;
+
+    fn next(&mut self) -> Option<&'a Message> {
+        self.range
+            .as_mut()
+            .and_then(|range| range.next())
+            .map(|(_, message)| message)
+    }
+}
+
+impl<'a> DoubleEndedIterator for ChannelIter<'a> {
+    fn next_back(&mut self) -> Option<&'a Message> {
+        self.range
+            .as_mut()
+            .and_then(|range| range.next_back())
+            .map(|(_, message)| message)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -58,6 +311,10 @@ mod tests {
             message_id: "1110000.0000".into(),
             thread_id: "1110000.0000".into(),
             channel_id: "C1".into(),
+            is_edited: false,
+            is_broadcast: false,
+            reply_count: 0,
+            reactions: Vec::new(),
         });
         message_buffer.add(StandardMessage {
             user_id: "U55".into(),
@@ -65,6 +322,10 @@ mod tests {
             message_id: "1110001.0000".into(),
             thread_id: "1110001.0000".into(),
             channel_id: "C1".into(),
+            is_edited: false,
+            is_broadcast: false,
+            reply_count: 0,
+            reactions: Vec::new(),
         });
 
         let canvas = message_buffer.render_as_canvas(&state, 10);
@@ -89,6 +350,10 @@ Example   |
             message_id: "1110000.0000".into(),
             thread_id: "1110000.0000".into(),
             channel_id: "C1".into(),
+            is_edited: false,
+            is_broadcast: false,
+            reply_count: 0,
+            reactions: Vec::new(),
         });
 
         state.selected_channel_id = ChannelID::from("C1");
@@ -103,6 +368,319 @@ Hello World                                       |
         );
     }
 
+    #[test]
+    fn it_renders_channel_start_before_messages() {
+        let mut state = AppState::fixture();
+        state.selected_channel_id = ChannelID::from("C1");
+
+        let mut message_buffer = MessageBuffer::new();
+        message_buffer.add(StandardMessage {
+            user_id: "U55".into(),
+            body: "First!".into(),
+            message_id: "1110000.0000".into(),
+            thread_id: "1110000.0000".into(),
+            channel_id: "C1".into(),
+            is_edited: false,
+            is_broadcast: false,
+            reply_count: 0,
+            reactions: Vec::new(),
+        });
+        message_buffer.mark_channel_start(&"C1".into());
+
+        assert!(message_buffer.has_channel_start(&"C1".into()));
+        assert!(!message_buffer.has_channel_start(&"C2".into()));
+        assert_eq!(
+            message_buffer.oldest_message_id(&"C1".into()),
+            Some(&MessageID::from("1110000.0000"))
+        );
+
+        let canvas = message_buffer.render_as_canvas(&state, 50);
+        assert_eq!(
+            &canvas.render_to_string(Some("|")),
+            "    This is the very beginning of the channel     |
+                                                  |
+U55                                               |
+First!                                            |
+                                                  |"
+        );
+    }
+
+    fn message(channel_id: &str, id: &str) -> StandardMessage {
+        StandardMessage {
+            user_id: "U55".into(),
+            body: format!("Message {}", id),
+            message_id: id.into(),
+            thread_id: id.into(),
+            channel_id: channel_id.into(),
+            is_edited: false,
+            is_broadcast: false,
+            reply_count: 0,
+            reactions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn it_stores_messages_with_same_id_per_channel() {
+        let mut message_buffer = MessageBuffer::new();
+        message_buffer.add(message("C1", "1110000.0000"));
+        message_buffer.add(message("C2", "1110000.0000"));
+        message_buffer.add(message("C2", "1110001.0000"));
+
+        assert_eq!(message_buffer.channel_iter(&"C1".into()).count(), 1);
+        assert_eq!(message_buffer.channel_iter(&"C2".into()).count(), 2);
+        assert_eq!(message_buffer.channel_iter(&"C3".into()).count(), 0);
+        assert!(
+            message_buffer
+                .get(&"C1".into(), &"1110000.0000".into())
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn it_finds_oldest_and_newest_message_per_channel() {
+        let mut message_buffer = MessageBuffer::new();
+        message_buffer.add(message("C1", "1110002.0000"));
+        message_buffer.add(message("C1", "1110000.0000"));
+        message_buffer.add(message("C1", "1110001.0000"));
+        message_buffer.add(message("C2", "1100000.0000"));
+        message_buffer.add(message("C2", "1120000.0000"));
+        message_buffer.mark_channel_start(&"C1".into());
+
+        let c1 = ChannelID::from("C1");
+        assert_eq!(
+            message_buffer.oldest_message(&c1).map(|m| m.id()),
+            Some(&MessageID::from("1110000.0000"))
+        );
+        assert_eq!(
+            message_buffer
+                .newest_channel_message(&c1)
+                .map(|m| &m.message_id),
+            Some(&MessageID::from("1110002.0000"))
+        );
+        assert!(message_buffer.oldest_message(&"C3".into()).is_none());
+        assert!(message_buffer.newest_channel_message(&"C3".into()).is_none());
+    }
+
+    #[test]
+    fn it_skips_local_entries_and_thread_replies_when_finding_the_newest_message() {
+        let mut message_buffer = MessageBuffer::new();
+        message_buffer.add(message("C1", "1110000.0000"));
+        message_buffer.add(StandardMessage {
+            thread_id: "1110000.0000".into(),
+            ..message("C1", "1110001.0000")
+        });
+        message_buffer.add(ErrorMessage {
+            id: "1110002.0000".into(),
+            channel_id: "C1".into(),
+            text: "Oops".into(),
+        });
+
+        assert_eq!(
+            message_buffer
+                .newest_channel_message(&"C1".into())
+                .map(|m| &m.message_id),
+            Some(&MessageID::from("1110000.0000"))
+        );
+    }
+
+    #[test]
+    fn it_edits_messages_in_place() {
+        let mut message_buffer = MessageBuffer::new();
+        message_buffer.add(message("C1", "1110000.0000"));
+
+        assert!(message_buffer.edit_message(
+            &"C1".into(),
+            &"1110000.0000".into(),
+            String::from("New body")
+        ));
+        assert!(!message_buffer.edit_message(
+            &"C2".into(),
+            &"1110000.0000".into(),
+            String::from("Wrong channel")
+        ));
+
+        match message_buffer.get(&"C1".into(), &"1110000.0000".into()) {
+            Some(&Message::Standard(ref message)) => {
+                assert_eq!(&message.body, "New body");
+                assert!(message.is_edited);
+            }
+            other => panic!("Unexpected message: {:?}", other),
+        }
+        assert_eq!(message_buffer.channel_iter(&"C1".into()).count(), 1);
+    }
+
+    #[test]
+    fn it_removes_messages() {
+        let mut message_buffer = MessageBuffer::new();
+        message_buffer.add(message("C1", "1110000.0000"));
+        message_buffer.add(message("C1", "1110001.0000"));
+
+        assert!(
+            message_buffer
+                .remove(&"C2".into(), &"1110000.0000".into())
+                .is_none()
+        );
+        assert!(
+            message_buffer
+                .remove(&"C1".into(), &"1110000.0000".into())
+                .is_some()
+        );
+        assert_eq!(message_buffer.channel_iter(&"C1".into()).count(), 1);
+        assert_eq!(
+            message_buffer.oldest_message_id(&"C1".into()),
+            Some(&MessageID::from("1110001.0000"))
+        );
+    }
+
+    #[test]
+    fn it_iterates_over_a_range_in_a_channel() {
+        let mut message_buffer = MessageBuffer::new();
+        message_buffer.add(message("C1", "1110000.0000"));
+        message_buffer.add(message("C1", "1110001.0000"));
+        message_buffer.add(message("C1", "1110002.0000"));
+        message_buffer.add(message("C2", "1110001.5000"));
+
+        let from = MessageID::from("1110001.0000");
+        let ids: Vec<&str> = message_buffer
+            .channel_range(&"C1".into(), from..)
+            .map(|m| m.id().as_str())
+            .collect();
+        assert_eq!(&ids, &["1110001.0000", "1110002.0000"]);
+
+        let reversed: Vec<&str> = message_buffer
+            .channel_iter(&"C1".into())
+            .rev()
+            .map(|m| m.id().as_str())
+            .collect();
+        assert_eq!(&reversed, &["1110002.0000", "1110001.0000", "1110000.0000"]);
+
+        assert_eq!(message_buffer.channel_iter(&"C3".into()).count(), 0);
+    }
+
+    #[test]
+    fn it_shows_thread_replies_only_in_the_thread() {
+        let mut state = AppState::fixture();
+        state.selected_channel_id = ChannelID::from("C1");
+
+        let mut message_buffer = MessageBuffer::new();
+        let mut parent = message("C1", "1110000.0000");
+        parent.reply_count = 1;
+        message_buffer.add(parent);
+        message_buffer.add(message("C1", "1110001.0000"));
+        message_buffer.add(StandardMessage {
+            thread_id: "1110000.0000".into(),
+            ..message("C1", "1110002.0000")
+        });
+
+        let channel = message_buffer.render_as_canvas(&state, 30);
+        assert_eq!(
+            &channel.render_to_string(Some("|")),
+            "U55                           |
+Message 1110000.0000          |
+1 reply                       |
+                              |
+U55                           |
+Message 1110001.0000          |
+                              |"
+        );
+
+        let thread_id = MessageID::from("1110000.0000");
+        let thread = message_buffer.render_thread_as_canvas(&state, &"C1".into(), &thread_id, 30);
+        assert_eq!(
+            &thread.render_to_string(Some("|")),
+            "U55                           |
+Message 1110000.0000          |
+1 reply                       |
+                              |
+U55                           |
+Message 1110002.0000          |
+                              |"
+        );
+    }
+
+    #[test]
+    fn it_shows_broadcast_replies_in_the_channel() {
+        let mut state = AppState::fixture();
+        state.selected_channel_id = ChannelID::from("C1");
+
+        let mut message_buffer = MessageBuffer::new();
+        message_buffer.add(message("C1", "1110000.0000"));
+        message_buffer.add(StandardMessage {
+            thread_id: "1110000.0000".into(),
+            is_broadcast: true,
+            ..message("C1", "1110001.0000")
+        });
+
+        let channel = message_buffer.render_as_canvas(&state, 30);
+        assert_eq!(
+            &channel.render_to_string(Some("|")),
+            "U55                           |
+Message 1110000.0000          |
+                              |
+U55                           |
+Message 1110001.0000          |
+                              |"
+        );
+    }
+
+    #[test]
+    fn it_records_rows_of_messages() {
+        let mut state = AppState::fixture();
+        state.selected_channel_id = ChannelID::from("C1");
+
+        let mut message_buffer = MessageBuffer::new();
+        message_buffer.add(message("C1", "1110000.0000"));
+        message_buffer.add(message("C1", "1110001.0000"));
+
+        // Each message wraps to three rows, followed by an empty row.
+        message_buffer.render_as_canvas(&state, 10);
+        assert_eq!(
+            *state.message_rows.borrow(),
+            vec![
+                MessageRows {
+                    id: "1110000.0000".into(),
+                    rows: 0..3,
+                },
+                MessageRows {
+                    id: "1110001.0000".into(),
+                    rows: 4..7,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn it_highlights_the_selected_message() {
+        use tui::style::Color;
+
+        let mut state = AppState::fixture();
+        state.selected_channel_id = ChannelID::from("C1");
+        state.selected_message = Some("1110001.0000".into());
+
+        let mut message_buffer = MessageBuffer::new();
+        message_buffer.add(message("C1", "1110000.0000"));
+        message_buffer.add(message("C1", "1110001.0000"));
+
+        let canvas = message_buffer.render_as_canvas(&state, 30);
+        assert_eq!(canvas.get_pos(0, 0).unwrap().style.bg, Color::Reset);
+        assert_eq!(canvas.get_pos(0, 3).unwrap().style.bg, Color::DarkGray);
+        assert_eq!(canvas.get_pos(29, 4).unwrap().style.bg, Color::DarkGray);
+    }
+
+    #[test]
+    fn it_updates_reply_counts() {
+        let mut message_buffer = MessageBuffer::new();
+        message_buffer.add(message("C1", "1110000.0000"));
+
+        assert!(message_buffer.set_reply_count(&"C1".into(), &"1110000.0000".into(), 3));
+        assert!(!message_buffer.set_reply_count(&"C1".into(), &"1110001.0000".into(), 3));
+
+        match message_buffer.get(&"C1".into(), &"1110000.0000".into()) {
+            Some(&Message::Standard(ref message)) => assert_eq!(message.reply_count, 3),
+            other => panic!("Unexpected message: {:?}", other),
+        }
+    }
+
     #[test]
     fn it_skips_messages_in_other_channels() {
         let mut state = AppState::fixture();
@@ -115,6 +693,10 @@ Hello World                                       |
             message_id: "1110000.0000".into(),
             thread_id: "1110000.0000".into(),
             channel_id: "C1".into(),
+            is_edited: false,
+            is_broadcast: false,
+            reply_count: 0,
+            reactions: Vec::new(),
         });
         message_buffer.add(StandardMessage {
             user_id: "Example".into(),
@@ -122,6 +704,10 @@ Hello World                                       |
             message_id: "1110000.0000".into(),
             thread_id: "1110000.0000".into(),
             channel_id: "C2".into(),
+            is_edited: false,
+            is_broadcast: false,
+            reply_count: 0,
+            reactions: Vec::new(),
         });
 
         let canvas = message_buffer.render_as_canvas(&state, 50);
@@ -0,0 +1,138 @@
+use std::cmp::{Ord, Ordering, PartialOrd};
+use std::hash::{Hash, Hasher};
+
+use failure::Error;
+
+use super::markup;
+use super::prelude::*;
+
+/// Something that happened to the channel itself, like a user joining or the topic changing.
+///
+/// Slack provides a text describing the event, like "<@U123|alice> has joined the group".
+#[derive(Clone, Debug)]
+pub struct ChannelEventMessage {
+    pub id: MessageID,
+    pub channel_id: ChannelID,
+    pub text: String,
+}
+
+impl Hash for ChannelEventMessage {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state)
+    }
+}
+
+impl PartialEq for ChannelEventMessage {
+    fn eq(&self, rhs: &ChannelEventMessage) -> bool {
+        self.id.eq(&rhs.id)
+    }
+}
+
+impl Eq for ChannelEventMessage {}
+
+impl PartialOrd for ChannelEventMessage {
+    fn partial_cmp(&self, rhs: &ChannelEventMessage) -> Option<Ordering> {
+        self.id.partial_cmp(&rhs.id)
+    }
+}
+
+impl Ord for ChannelEventMessage {
+    fn cmp(&self, rhs: &ChannelEventMessage) -> Ordering {
+        self.id.cmp(&rhs.id)
+    }
+}
+
+impl ChannelEventMessage {
+    /// Returns `None` when the channel is not known, which is the case for these messages when
+    /// they arrive over RTM.
+    pub fn from_slack_message(
+        id: &Option<String>,
+        text: &Option<String>,
+        side_channel: &MessageSideChannel,
+    ) -> Result<Option<ChannelEventMessage>, Error> {
+        let channel_id = match side_channel.channel_id {
+            Some(ref channel_id) => channel_id.clone(),
+            None => return Ok(None),
+        };
+
+        Ok(Some(ChannelEventMessage {
+            id: id
+                .clone()
+                .map(MessageID::from)
+                .ok_or_else(|| format_err!("ID was blank"))?,
+            channel_id,
+            text: text.clone().unwrap_or_else(String::new),
+        }))
+    }
+}
+
+impl HistoryEntry for ChannelEventMessage {
+    fn id(&self) -> &MessageID {
+        &self.id
+    }
+
+    fn channel_id(&self) -> &ChannelID {
+        &self.channel_id
+    }
+
+    fn render_as_canvas(&self, state: &AppState, width: u16) -> Canvas {
+        use tui::style::*;
+
+        let mut canvas = Canvas::new(width);
+        markup::render(
+            &mut canvas,
+            &markup::parse(&self.text),
+            Style::default().modifier(Modifier::Italic),
+            state,
+        );
+        canvas.add_string_wrapped("\n", Style::default());
+
+        canvas
+    }
+
+    fn into_message(self) -> Message {
+        Message::ChannelEvent(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use models::User;
+
+    #[test]
+    fn it_renders_as_canvas() {
+        let mut state = AppState::fixture();
+        state.users.add_user(User::fixture("U55", "alice"));
+        let message = ChannelEventMessage {
+            id: "1110000.000000".into(),
+            channel_id: "C1".into(),
+            text: "<@U55|alice> has joined the group".into(),
+        };
+
+        let canvas = message.render_as_canvas(&state, 30);
+        assert_eq!(
+            &canvas.render_to_string(Some("|")),
+            "@alice has joined the group   |"
+        );
+    }
+
+    #[test]
+    fn it_skips_messages_without_a_channel() {
+        let id = Some(String::from("1110000.000000"));
+        let text = Some(String::from("<@U55|alice> has left the group"));
+
+        let message =
+            ChannelEventMessage::from_slack_message(&id, &text, &MessageSideChannel::default());
+        assert!(message.unwrap().is_none());
+
+        let side_channel = MessageSideChannel {
+            channel_id: Some("G1".into()),
+            ..Default::default()
+        };
+        let message = ChannelEventMessage::from_slack_message(&id, &text, &side_channel)
+            .unwrap()
+            .unwrap();
+        assert_eq!(message.channel_id, ChannelID::from("G1"));
+    }
+}
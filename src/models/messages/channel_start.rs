@@ -0,0 +1,114 @@
+use std::cmp::{Ord, Ordering, PartialOrd};
+use std::hash::{Hash, Hasher};
+
+use super::prelude::*;
+
+/// Marks the beginning of a channel's history, when there are no older messages to load.
+#[derive(Clone, Debug)]
+pub struct ChannelStartMessage {
+    pub id: MessageID,
+    pub channel_id: ChannelID,
+}
+
+impl Hash for ChannelStartMessage {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state)
+    }
+}
+
+impl PartialEq for ChannelStartMessage {
+    fn eq(&self, rhs: &ChannelStartMessage) -> bool {
+        self.id.eq(&rhs.id)
+    }
+}
+
+impl Eq for ChannelStartMessage {}
+
+impl PartialOrd for ChannelStartMessage {
+    fn partial_cmp(&self, rhs: &ChannelStartMessage) -> Option<Ordering> {
+        self.id.partial_cmp(&rhs.id)
+    }
+}
+
+impl Ord for ChannelStartMessage {
+    fn cmp(&self, rhs: &ChannelStartMessage) -> Ordering {
+        self.id.cmp(&rhs.id)
+    }
+}
+
+impl ChannelStartMessage {
+    pub fn new(channel_id: &ChannelID) -> ChannelStartMessage {
+        ChannelStartMessage {
+            id: ChannelStartMessage::id_for_channel(channel_id),
+            channel_id: channel_id.clone(),
+        }
+    }
+
+    /// Generates an ID that sorts before any real Slack timestamp, while still being unique per
+    /// channel.
+    pub fn id_for_channel(channel_id: &ChannelID) -> MessageID {
+        MessageID::from(format!("0.{}", channel_id.as_str()))
+    }
+}
+
+impl HistoryEntry for ChannelStartMessage {
+    fn id(&self) -> &MessageID {
+        &self.id
+    }
+
+    fn channel_id(&self) -> &ChannelID {
+        &self.channel_id
+    }
+
+    fn render_as_canvas(&self, state: &AppState, width: u16) -> Canvas {
+        use tui::style::*;
+
+        let text = match state.channels.get(&self.channel_id) {
+            Some(channel) => format!(
+                "This is the very beginning of {}{}",
+                channel.sigil(),
+                channel.name()
+            ),
+            None => String::from("This is the very beginning of the channel"),
+        };
+
+        let mut canvas = Canvas::new(width);
+        canvas.add_string_wrapped(
+            &format!("{:^1$}\n", text, width as usize),
+            Style::default().fg(Color::Cyan).modifier(Modifier::Bold),
+        );
+
+        canvas
+    }
+
+    fn into_message(self) -> Message {
+        Message::ChannelStart(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use models::Channel;
+
+    #[test]
+    fn it_renders_as_canvas() {
+        let mut state = AppState::fixture();
+        state.channels.add_channel(Channel::fixture("C1", "general"));
+        let message = ChannelStartMessage::new(&"C1".into());
+
+        let canvas = message.render_as_canvas(&state, 50);
+        assert_eq!(
+            &canvas.render_to_string(Some("|")),
+            "      This is the very beginning of #general      |"
+        );
+    }
+
+    #[test]
+    fn it_sorts_before_real_messages() {
+        let message = ChannelStartMessage::new(&"C1".into());
+        let first_real_id = MessageID::from("1000000000.000000");
+
+        assert!(message.id() < &first_real_id);
+    }
+}
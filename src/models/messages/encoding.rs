@@ -0,0 +1,261 @@
+//! Encoding of text written by the user into the format Slack expects when sending messages.
+//!
+//! This is the inverse of the entity reference parsing in `markup`: `@alice` and `#general` are
+//! turned into `<@U123>` and `<#C123>` references, and characters that have special meaning to
+//! Slack are escaped so the user cannot accidentally write control sequences.
+//!
+//! `decode` goes the other way, turning a message body back into text the user can edit.
+
+use models::messages::markup::{self, Entity};
+use models::{ChannelList, UserList};
+
+const SPECIAL_MENTIONS: &[&str] = &["here", "channel", "everyone"];
+
+pub fn encode(text: &str, users: &UserList, channels: &ChannelList) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    let mut previous: Option<char> = None;
+    let mut rest = text;
+
+    while let Some(chr) = rest.chars().next() {
+        let at_word_boundary = previous.map(|c| !c.is_alphanumeric()).unwrap_or(true);
+
+        let reference = if at_word_boundary && (chr == '@' || chr == '#') {
+            encode_reference(chr, &rest[1..], users, channels)
+        } else {
+            None
+        };
+
+        match reference {
+            Some((reference, name_length)) => {
+                encoded.push_str(&reference);
+                rest = &rest[1 + name_length..];
+                previous = Some('>');
+            }
+            None => {
+                match chr {
+                    '&' => encoded.push_str("&amp;"),
+                    '<' => encoded.push_str("&lt;"),
+                    '>' => encoded.push_str("&gt;"),
+                    _ => encoded.push(chr),
+                }
+                rest = &rest[chr.len_utf8()..];
+                previous = Some(chr);
+            }
+        }
+    }
+
+    encoded
+}
+
+/// Turns a message body from Slack into the text the user would have written, so that encoding it
+/// again gives an equivalent message.
+///
+/// Formatting markers are kept as they are, so only entity references and escapes are decoded.
+pub fn decode(text: &str, users: &UserList, channels: &ChannelList) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut decoded = String::with_capacity(text.len());
+    let mut plain = String::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        match markup::find_entity_end(&chars, i) {
+            Some(end) => {
+                let content: String = chars[i + 1..end].iter().collect();
+                decoded.push_str(&markup::unescape(&plain));
+                decoded.push_str(&decode_entity(
+                    markup::parse_entity(&content),
+                    users,
+                    channels,
+                ));
+                plain.clear();
+                i = end + 1;
+            }
+            None => {
+                plain.push(chars[i]);
+                i += 1;
+            }
+        }
+    }
+
+    decoded.push_str(&markup::unescape(&plain));
+    decoded
+}
+
+fn decode_entity(entity: Entity, users: &UserList, channels: &ChannelList) -> String {
+    let unescape_label = |label: Option<String>| label.map(|label| markup::unescape(&label));
+
+    match entity {
+        Entity::UserMention(id, label) => {
+            let name = users
+                .get(&id)
+                .map(|user| user.display_name().to_owned())
+                .or_else(|| unescape_label(label))
+                .unwrap_or_else(|| id.as_str().to_owned());
+            format!("@{}", name)
+        }
+        Entity::ChannelMention(id, label) => {
+            let name = channels
+                .get(&id)
+                .map(|channel| channel.name().to_owned())
+                .or_else(|| unescape_label(label))
+                .unwrap_or_else(|| id.as_str().to_owned());
+            format!("#{}", name)
+        }
+        Entity::UserGroupMention(id, label) => unescape_label(label).unwrap_or(id),
+        Entity::SpecialMention(name) => format!("@{}", name),
+        Entity::Command(label) => markup::unescape(&label),
+        // Labels of links cannot be written by the user, so only the URL is kept. Slack turns it
+        // into a link again when the message is sent.
+        Entity::Link { url, .. } => markup::unescape(&url),
+    }
+}
+
+/// Tries to find a user or channel with a name at the start of `text`. Returns the encoded
+/// reference and the length of the name that was used.
+fn encode_reference(
+    sigil: char,
+    text: &str,
+    users: &UserList,
+    channels: &ChannelList,
+) -> Option<(String, usize)> {
+    let mut name = &text[..name_length(text)];
+
+    // Try the longest name first, then trim away punctuation that could end a sentence instead;
+    // "@alice." should still mention "alice".
+    loop {
+        if name.is_empty() {
+            return None;
+        }
+
+        let reference = match sigil {
+            '@' if SPECIAL_MENTIONS.contains(&name) => Some(format!("<!{}>", name)),
+            '@' => users
+                .find_by_name(name)
+                .map(|user| format!("<@{}>", user.id().as_str())),
+            _ => channels
+                .find_by_name(name)
+                .filter(|channel| !channel.kind().is_direct_message())
+                .map(|channel| format!("<#{}>", channel.id().as_str())),
+        };
+
+        if let Some(reference) = reference {
+            return Some((reference, name.len()));
+        }
+
+        match name.chars().next_back() {
+            Some('.') | Some('-') | Some('_') => name = &name[..name.len() - 1],
+            _ => return None,
+        }
+    }
+}
+
+fn name_length(text: &str) -> usize {
+    text.find(|c: char| !(c.is_alphanumeric() || c == '.' || c == '-' || c == '_'))
+        .unwrap_or_else(|| text.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use models::messages::markup::Span;
+    use models::{Channel, ChannelID, User, UserID};
+
+    fn fixtures() -> (UserList, ChannelList) {
+        let mut users = UserList::new();
+        users.add_user(User::fixture("U1", "alice"));
+        users.add_user(User::fixture("U2", "bob.smith"));
+
+        let mut channels = ChannelList::new();
+        channels.add_channel(Channel::fixture("C1", "general"));
+        channels.add_channel(Channel::fixture("C2", "dev-ops"));
+
+        (users, channels)
+    }
+
+    fn encode_with_fixtures(text: &str) -> String {
+        let (users, channels) = fixtures();
+        encode(text, &users, &channels)
+    }
+
+    fn decode_with_fixtures(text: &str) -> String {
+        let (users, channels) = fixtures();
+        decode(text, &users, &channels)
+    }
+
+    #[test]
+    fn it_keeps_plain_text() {
+        assert_eq!(encode_with_fixtures("Hello world"), "Hello world");
+        assert_eq!(encode_with_fixtures(""), "");
+    }
+
+    #[test]
+    fn it_escapes_control_characters() {
+        assert_eq!(
+            encode_with_fixtures("a < b && c > d <@U1>"),
+            "a &lt; b &amp;&amp; c &gt; d &lt;@U1&gt;"
+        );
+    }
+
+    #[test]
+    fn it_encodes_user_mentions() {
+        assert_eq!(encode_with_fixtures("@alice hi"), "<@U1> hi");
+        assert_eq!(encode_with_fixtures("cc @bob.smith."), "cc <@U2>.");
+        assert_eq!(encode_with_fixtures("@Alice, @alice!"), "<@U1>, <@U1>!");
+    }
+
+    #[test]
+    fn it_encodes_special_mentions() {
+        assert_eq!(
+            encode_with_fixtures("@here @channel @everyone"),
+            "<!here> <!channel> <!everyone>"
+        );
+    }
+
+    #[test]
+    fn it_encodes_channel_references() {
+        assert_eq!(
+            encode_with_fixtures("See #general and #dev-ops."),
+            "See <#C1> and <#C2>."
+        );
+    }
+
+    #[test]
+    fn it_keeps_unknown_names_and_non_mentions() {
+        assert_eq!(encode_with_fixtures("@nobody #nowhere"), "@nobody #nowhere");
+        assert_eq!(encode_with_fixtures("mail alice@alice.com"), "mail alice@alice.com");
+        assert_eq!(encode_with_fixtures("issue#general"), "issue#general");
+        assert_eq!(encode_with_fixtures("@ #"), "@ #");
+    }
+
+    #[test]
+    fn it_is_the_inverse_of_parsing() {
+        assert_eq!(
+            markup::parse(&encode_with_fixtures("1 < 2 & @alice in #general")),
+            vec![
+                Span::Text(String::from("1 < 2 & ")),
+                Span::UserMention(UserID::from("U1"), None),
+                Span::Text(String::from(" in ")),
+                Span::ChannelMention(ChannelID::from("C1"), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_decodes_references_and_escapes() {
+        assert_eq!(
+            decode_with_fixtures("<@U1> &lt;3 <#C2|dev-ops> &amp; <!here>"),
+            "@alice <3 #dev-ops & @here"
+        );
+        assert_eq!(
+            decode_with_fixtures("<@U9|carol> in <#C9|random>, see <https://a.b|label>"),
+            "@carol in #random, see https://a.b"
+        );
+        assert_eq!(decode_with_fixtures("a <b\n<@U2>"), "a <b\n@bob.smith");
+    }
+
+    #[test]
+    fn it_decodes_what_it_encodes() {
+        let text = "@alice: 1 < 2 & #general is @here.";
+        assert_eq!(decode_with_fixtures(&encode_with_fixtures(text)), text);
+    }
+}
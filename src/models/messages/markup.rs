@@ -0,0 +1,644 @@
+//! Parsing and rendering of Slack's "mrkdwn" message markup.
+//!
+//! Formatting markers are only recognized when they look intentional; `2 * 3 * 4` and
+//! `snake_case_name` are kept as literal text. Markers never span multiple lines, and unmatched
+//! markers are kept as literal text.
+//!
+//! Entity references (`<@U123>`, `<#C123|general>`, `<https://example.com|label>`, ...) are parsed
+//! into their own spans, and HTML escapes are decoded, so the text in spans is what the user
+//! should see.
+
+use tui::style::{Color, Modifier, Style};
+
+use models::{AppState, Canvas, ChannelID, UserID};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Span {
+    Text(String),
+    Bold(Vec<Span>),
+    Italic(Vec<Span>),
+    Strike(Vec<Span>),
+    Code(String),
+    Preformatted(String),
+    Quote(Vec<Span>),
+    UserMention(UserID, Option<String>),
+    ChannelMention(ChannelID, Option<String>),
+    UserGroupMention(String, Option<String>),
+    /// `@here`, `@channel` or `@everyone`.
+    SpecialMention(String),
+    Link { url: String, label: Option<String> },
+}
+
+/// A reference or link between `<` and `>` in message markup.
+pub(crate) enum Entity {
+    UserMention(UserID, Option<String>),
+    ChannelMention(ChannelID, Option<String>),
+    UserGroupMention(String, Option<String>),
+    SpecialMention(String),
+    /// Other commands, like dates, shown as their fallback label.
+    Command(String),
+    Link { url: String, label: Option<String> },
+}
+
+pub fn parse(text: &str) -> Vec<Span> {
+    let mut spans = Vec::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find("```") {
+        let code_start = start + 3;
+        match rest[code_start..].find("```") {
+            Some(length) => {
+                let code_end = code_start + length;
+                push_all(&mut spans, parse_lines(&rest[..start]));
+                spans.push(Span::Preformatted(
+                    rest[code_start..code_end].trim_matches('\n').to_owned(),
+                ));
+                rest = &rest[code_end + 3..];
+            }
+            None => break,
+        }
+    }
+
+    push_all(&mut spans, parse_lines(rest));
+    unescape_spans(spans)
+}
+
+pub fn render(canvas: &mut Canvas, spans: &[Span], style: Style, state: &AppState) {
+    for (i, span) in spans.iter().enumerate() {
+        match *span {
+            Span::Preformatted(ref code) => {
+                let code_style = style.clone().fg(Color::Yellow);
+                if i > 0 && !ends_with_newline(&spans[i - 1]) {
+                    canvas.add_string_wrapped("\n", style);
+                }
+                canvas.add_string_wrapped(code, code_style);
+                if i + 1 < spans.len() && !starts_with_newline(&spans[i + 1]) {
+                    canvas.add_string_wrapped("\n", style);
+                }
+            }
+            _ => render_span(canvas, span, style, state),
+        }
+    }
+}
+
+/// Width of the bar in front of quoted lines.
+const QUOTE_PREFIX_WIDTH: u16 = 2;
+
+// A cell can only have one modifier in tui, so nested formatting like `*_both_*` shows the
+// innermost one.
+fn render_span(canvas: &mut Canvas, span: &Span, style: Style, state: &AppState) {
+    match *span {
+        Span::Text(ref text) => canvas.add_string_wrapped(text, style),
+        Span::Bold(ref children) => render(canvas, children, style.modifier(Modifier::Bold), state),
+        Span::Italic(ref children) => {
+            render(canvas, children, style.modifier(Modifier::Italic), state)
+        }
+        Span::Strike(ref children) => {
+            render(canvas, children, style.modifier(Modifier::CrossedOut), state)
+        }
+        Span::Code(ref code) => canvas.add_string_wrapped(code, style.fg(Color::Yellow)),
+        Span::Preformatted(ref code) => canvas.add_string_wrapped(code, style.fg(Color::Yellow)),
+        Span::Quote(ref children) if canvas.width() > QUOTE_PREFIX_WIDTH => {
+            // Render the quote on its own so every line of it, wrapped or not, gets the bar.
+            let mut quote = Canvas::new(canvas.width() - QUOTE_PREFIX_WIDTH);
+            render(&mut quote, children, style.fg(Color::Gray), state);
+            canvas.add_prefixed_lines("│ ", style.fg(Color::Gray), &quote);
+        }
+        Span::Quote(ref children) => render(canvas, children, style.fg(Color::Gray), state),
+        Span::UserMention(ref id, ref label) => {
+            let name = match (state.users.get(id), label) {
+                (Some(user), _) => user.display_name(),
+                (None, &Some(ref label)) => label,
+                (None, &None) => id.as_str(),
+            };
+            let is_self = id == &state.current_user_id;
+            canvas.add_string_wrapped(&format!("@{}", name), mention_style(style, is_self));
+        }
+        Span::ChannelMention(ref id, ref label) => {
+            let name = match (state.channels.get(id), label) {
+                (Some(channel), _) => channel.name(),
+                (None, &Some(ref label)) => label,
+                (None, &None) => id.as_str(),
+            };
+            canvas.add_string_wrapped(&format!("#{}", name), mention_style(style, false));
+        }
+        Span::UserGroupMention(ref id, ref label) => {
+            let name = match *label {
+                Some(ref label) => label.clone(),
+                None => format!("@{}", id),
+            };
+            canvas.add_string_wrapped(&name, mention_style(style, false));
+        }
+        Span::SpecialMention(ref name) => {
+            canvas.add_string_wrapped(&format!("@{}", name), mention_style(style, true));
+        }
+        Span::Link { ref url, ref label } => {
+            let text = label.as_ref().unwrap_or(url);
+            canvas.add_string_wrapped(
+                text,
+                style.fg(Color::LightCyan).modifier(Modifier::Underline),
+            );
+        }
+    }
+}
+
+/// Returns true if the spans mention the user, either directly or with `@here`, `@channel` or
+/// `@everyone`.
+pub fn mentions(spans: &[Span], user_id: &UserID) -> bool {
+    spans.iter().any(|span| match *span {
+        Span::UserMention(ref id, _) => id == user_id,
+        Span::SpecialMention(_) => true,
+        Span::Bold(ref children)
+        | Span::Italic(ref children)
+        | Span::Strike(ref children)
+        | Span::Quote(ref children) => mentions(children, user_id),
+        _ => false,
+    })
+}
+
+fn mention_style(style: Style, is_self: bool) -> Style {
+    if is_self {
+        style.fg(Color::Black).bg(Color::Yellow)
+    } else {
+        style.fg(Color::Cyan)
+    }
+}
+
+fn ends_with_newline(span: &Span) -> bool {
+    match *span {
+        Span::Text(ref text) => text.ends_with('\n'),
+        _ => false,
+    }
+}
+
+fn starts_with_newline(span: &Span) -> bool {
+    match *span {
+        Span::Text(ref text) => text.starts_with('\n'),
+        _ => false,
+    }
+}
+
+fn parse_lines(text: &str) -> Vec<Span> {
+    let mut spans = Vec::new();
+    let mut line_start = 0;
+
+    for (i, line) in text.split('\n').enumerate() {
+        if i > 0 {
+            push_span(&mut spans, Span::Text(String::from("\n")));
+        }
+
+        if let Some(quoted) = strip_quote_prefix(line, "&gt;&gt;&gt;")
+            .or_else(|| strip_quote_prefix(line, ">>>"))
+        {
+            // Quotes everything to the end of the text
+            let quote_start = line_start + (line.len() - quoted.len());
+            spans.push(Span::Quote(parse_lines(&text[quote_start..])));
+            break;
+        } else if let Some(quoted) =
+            strip_quote_prefix(line, "&gt;").or_else(|| strip_quote_prefix(line, ">"))
+        {
+            spans.push(Span::Quote(parse_inline(quoted)));
+        } else {
+            push_all(&mut spans, parse_inline(line));
+        }
+
+        line_start += line.len() + 1;
+    }
+
+    spans
+}
+
+fn strip_quote_prefix<'a>(line: &'a str, prefix: &str) -> Option<&'a str> {
+    if line.starts_with(prefix) {
+        let rest = &line[prefix.len()..];
+        Some(if rest.starts_with(' ') { &rest[1..] } else { rest })
+    } else {
+        None
+    }
+}
+
+fn parse_inline(text: &str) -> Vec<Span> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut spans = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let chr = chars[i];
+        if let Some(end) = find_entity_end(&chars, i) {
+            let content: String = chars[i + 1..end].iter().collect();
+            spans.push(parse_entity(&content).into());
+            i = end + 1;
+        } else if let Some(end) = find_closing_marker(&chars, i) {
+            let inner: String = chars[i + 1..end].iter().collect();
+            let span = match chr {
+                '`' => Span::Code(inner),
+                '*' => Span::Bold(parse_inline(&inner)),
+                '_' => Span::Italic(parse_inline(&inner)),
+                '~' => Span::Strike(parse_inline(&inner)),
+                _ => unreachable!(),
+            };
+            spans.push(span);
+            i = end + 1;
+        } else {
+            let mut text = String::new();
+            text.push(chr);
+            push_span(&mut spans, Span::Text(text));
+            i += 1;
+        }
+    }
+
+    spans
+}
+
+/// If the character at `start` opens an entity reference, return the index of the closing `>`.
+pub(crate) fn find_entity_end(chars: &[char], start: usize) -> Option<usize> {
+    if chars[start] != '<' {
+        return None;
+    }
+
+    chars[start + 1..]
+        .iter()
+        .position(|&c| c == '>' || c == '\n')
+        .map(|length| start + 1 + length)
+        .filter(|&end| end > start + 1 && chars[end] == '>')
+}
+
+pub(crate) fn parse_entity(content: &str) -> Entity {
+    let (target, label) = match content.find('|') {
+        Some(pos) => (&content[..pos], Some(content[pos + 1..].to_owned())),
+        None => (content, None),
+    };
+
+    if target.starts_with('@') {
+        Entity::UserMention(UserID::from(&target[1..]), label)
+    } else if target.starts_with('#') {
+        Entity::ChannelMention(ChannelID::from(&target[1..]), label)
+    } else if target.starts_with("!subteam^") {
+        Entity::UserGroupMention(target["!subteam^".len()..].to_owned(), label)
+    } else if target.starts_with('!') {
+        match &target[1..] {
+            "here" | "channel" | "everyone" => Entity::SpecialMention(target[1..].to_owned()),
+            other => Entity::Command(label.unwrap_or_else(|| other.to_owned())),
+        }
+    } else {
+        Entity::Link {
+            url: target.to_owned(),
+            label,
+        }
+    }
+}
+
+impl From<Entity> for Span {
+    fn from(entity: Entity) -> Span {
+        match entity {
+            Entity::UserMention(id, label) => Span::UserMention(id, label),
+            Entity::ChannelMention(id, label) => Span::ChannelMention(id, label),
+            Entity::UserGroupMention(id, label) => Span::UserGroupMention(id, label),
+            Entity::SpecialMention(name) => Span::SpecialMention(name),
+            Entity::Command(label) => Span::Text(label),
+            Entity::Link { url, label } => Span::Link { url, label },
+        }
+    }
+}
+
+pub(crate) fn unescape(text: &str) -> String {
+    // &amp; must be last, or "&amp;lt;" would turn into "<" instead of "&lt;".
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+fn unescape_spans(spans: Vec<Span>) -> Vec<Span> {
+    spans.into_iter().map(unescape_span).collect()
+}
+
+fn unescape_span(span: Span) -> Span {
+    let unescape_label = |label: Option<String>| label.map(|label| unescape(&label));
+    match span {
+        Span::Text(text) => Span::Text(unescape(&text)),
+        Span::Bold(children) => Span::Bold(unescape_spans(children)),
+        Span::Italic(children) => Span::Italic(unescape_spans(children)),
+        Span::Strike(children) => Span::Strike(unescape_spans(children)),
+        Span::Code(code) => Span::Code(unescape(&code)),
+        Span::Preformatted(code) => Span::Preformatted(unescape(&code)),
+        Span::Quote(children) => Span::Quote(unescape_spans(children)),
+        Span::UserMention(id, label) => Span::UserMention(id, unescape_label(label)),
+        Span::ChannelMention(id, label) => Span::ChannelMention(id, unescape_label(label)),
+        Span::UserGroupMention(id, label) => Span::UserGroupMention(id, unescape_label(label)),
+        Span::SpecialMention(name) => Span::SpecialMention(name),
+        Span::Link { url, label } => Span::Link {
+            url: unescape(&url),
+            label: unescape_label(label),
+        },
+    }
+}
+
+/// If the character at `start` opens a formatting marker, return the index of the closing one.
+fn find_closing_marker(chars: &[char], start: usize) -> Option<usize> {
+    let marker = chars[start];
+    match marker {
+        '`' | '*' | '_' | '~' => {}
+        _ => return None,
+    }
+
+    // Opening markers must be at a word boundary and directly followed by the content.
+    let after_boundary = start == 0 || !chars[start - 1].is_alphanumeric();
+    let followed_by_content = chars
+        .get(start + 1)
+        .map(|c| !c.is_whitespace() && *c != marker)
+        .unwrap_or(false);
+    if !after_boundary || !followed_by_content {
+        return None;
+    }
+
+    for end in (start + 2)..chars.len() {
+        let chr = chars[end];
+        if chr == '\n' {
+            return None;
+        }
+
+        let preceded_by_content = !chars[end - 1].is_whitespace();
+        let before_boundary = chars
+            .get(end + 1)
+            .map(|c| !c.is_alphanumeric())
+            .unwrap_or(true);
+        if chr == marker && preceded_by_content && before_boundary {
+            return Some(end);
+        }
+    }
+
+    None
+}
+
+/// Pushes a span, merging it into the previous one if both are text.
+fn push_span(spans: &mut Vec<Span>, span: Span) {
+    if let Span::Text(ref new_text) = span {
+        if let Some(&mut Span::Text(ref mut text)) = spans.last_mut() {
+            text.push_str(new_text);
+            return;
+        }
+    }
+    spans.push(span);
+}
+
+fn push_all(spans: &mut Vec<Span>, new_spans: Vec<Span>) {
+    for span in new_spans {
+        push_span(spans, span);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(s: &str) -> Span {
+        Span::Text(String::from(s))
+    }
+
+    #[test]
+    fn it_parses_plain_text() {
+        assert_eq!(parse("Hello world"), vec![text("Hello world")]);
+        assert_eq!(parse(""), vec![]);
+    }
+
+    #[test]
+    fn it_parses_simple_formatting() {
+        assert_eq!(
+            parse("*bold* _italic_ ~strike~ `code`"),
+            vec![
+                Span::Bold(vec![text("bold")]),
+                text(" "),
+                Span::Italic(vec![text("italic")]),
+                text(" "),
+                Span::Strike(vec![text("strike")]),
+                text(" "),
+                Span::Code(String::from("code")),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_parses_nested_formatting() {
+        assert_eq!(
+            parse("*bold _and italic_*, ok?"),
+            vec![
+                Span::Bold(vec![text("bold "), Span::Italic(vec![text("and italic")])]),
+                text(", ok?"),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_does_not_format_inside_code() {
+        assert_eq!(
+            parse("`*not bold*` *bold*"),
+            vec![
+                Span::Code(String::from("*not bold*")),
+                text(" "),
+                Span::Bold(vec![text("bold")]),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_keeps_unmatched_markers() {
+        assert_eq!(parse("*not closed"), vec![text("*not closed")]);
+        assert_eq!(parse("2 * 3 * 4"), vec![text("2 * 3 * 4")]);
+        assert_eq!(parse("snake_case_name"), vec![text("snake_case_name")]);
+        assert_eq!(parse("**"), vec![text("**")]);
+        assert_eq!(parse("`"), vec![text("`")]);
+    }
+
+    #[test]
+    fn it_does_not_format_across_lines() {
+        assert_eq!(parse("*foo\nbar*"), vec![text("*foo\nbar*")]);
+    }
+
+    #[test]
+    fn it_parses_preformatted_blocks() {
+        assert_eq!(
+            parse("Look:\n```\nfn *main*() {}\n```\nNeat"),
+            vec![
+                text("Look:\n"),
+                Span::Preformatted(String::from("fn *main*() {}")),
+                text("\nNeat"),
+            ]
+        );
+        assert_eq!(parse("```unclosed"), vec![text("```unclosed")]);
+    }
+
+    #[test]
+    fn it_parses_quotes() {
+        assert_eq!(
+            parse("&gt; *quoted*\nnot quoted\n> also quoted"),
+            vec![
+                Span::Quote(vec![Span::Bold(vec![text("quoted")])]),
+                text("\nnot quoted\n"),
+                Span::Quote(vec![text("also quoted")]),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_parses_multiline_quotes() {
+        assert_eq!(
+            parse("Before\n&gt;&gt;&gt; first\nsecond"),
+            vec![
+                text("Before\n"),
+                Span::Quote(vec![text("first\nsecond")]),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_renders_spans_to_canvas() {
+        let mut canvas = Canvas::new(10);
+        let state = AppState::fixture();
+        render(
+            &mut canvas,
+            &parse("*Hi* `x`\n```y```"),
+            Style::default(),
+            &state,
+        );
+
+        assert_eq!(
+            &canvas.render_to_string(Some("|")),
+            "Hi x      |
+y         |"
+        );
+        let bold = Style::default().modifier(Modifier::Bold);
+        let code = Style::default().fg(Color::Yellow);
+        assert_eq!(canvas.get_pos(0, 0).unwrap().style, bold);
+        assert_eq!(canvas.get_pos(3, 0).unwrap().style, code);
+        assert_eq!(canvas.get_pos(0, 1).unwrap().style, code);
+    }
+
+    #[test]
+    fn it_parses_entity_references() {
+        assert_eq!(
+            parse("<@U1> <@U2|bob> in <#C1|general> cc <!subteam^S1|@devs> <!here>"),
+            vec![
+                Span::UserMention(UserID::from("U1"), None),
+                text(" "),
+                Span::UserMention(UserID::from("U2"), Some(String::from("bob"))),
+                text(" in "),
+                Span::ChannelMention(ChannelID::from("C1"), Some(String::from("general"))),
+                text(" cc "),
+                Span::UserGroupMention(String::from("S1"), Some(String::from("@devs"))),
+                text(" "),
+                Span::SpecialMention(String::from("here")),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_parses_links() {
+        assert_eq!(
+            parse("See <https://example.com/a_b_c?x=1&amp;y=2|the _docs_> and <https://x.y>"),
+            vec![
+                text("See "),
+                Span::Link {
+                    url: String::from("https://example.com/a_b_c?x=1&y=2"),
+                    label: Some(String::from("the _docs_")),
+                },
+                text(" and "),
+                Span::Link {
+                    url: String::from("https://x.y"),
+                    label: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn it_parses_entities_inside_formatting() {
+        assert_eq!(
+            parse("*hi <@U1>*"),
+            vec![Span::Bold(vec![
+                text("hi "),
+                Span::UserMention(UserID::from("U1"), None),
+            ])]
+        );
+    }
+
+    #[test]
+    fn it_unescapes_html_entities() {
+        assert_eq!(
+            parse("a &lt;b&gt; &amp;lt; `&amp;`"),
+            vec![text("a <b> &lt; "), Span::Code(String::from("&"))]
+        );
+        assert_eq!(parse("<>"), vec![text("<>")]);
+    }
+
+    #[test]
+    fn it_renders_mentions() {
+        use models::{Channel, User};
+
+        let mut state = AppState::fixture();
+        state.users.add_user(User::fixture("U1", "alice"));
+        state.users.add_user(User::fixture("U2", "bob"));
+        state.channels.add_channel(Channel::fixture("C1", "general"));
+        state.current_user_id = UserID::from("U2");
+
+        let mut canvas = Canvas::new(30);
+        render(
+            &mut canvas,
+            &parse("<@U1> <@U2> <#C1> <https://x.y|link>"),
+            Style::default(),
+            &state,
+        );
+
+        assert_eq!(
+            &canvas.render_to_string(Some("|")),
+            "@alice @bob #general link     |"
+        );
+        let other_mention = Style::default().fg(Color::Cyan);
+        let self_mention = Style::default().fg(Color::Black).bg(Color::Yellow);
+        assert_eq!(canvas.get_pos(0, 0).unwrap().style, other_mention);
+        assert_eq!(canvas.get_pos(7, 0).unwrap().style, self_mention);
+        assert_eq!(canvas.get_pos(12, 0).unwrap().style, other_mention);
+    }
+
+    #[test]
+    fn it_renders_the_bar_on_every_line_of_quotes() {
+        let state = AppState::fixture();
+        let mut canvas = Canvas::new(12);
+        render(
+            &mut canvas,
+            &parse("Before\n>>>first\nsecond line wraps"),
+            Style::default(),
+            &state,
+        );
+
+        assert_eq!(
+            &canvas.render_to_string(Some("|")),
+            "Before      |
+│ first     |
+│ second lin|
+│ e wraps   |"
+        );
+    }
+
+    #[test]
+    fn it_renders_the_innermost_modifier_of_nested_formatting() {
+        let state = AppState::fixture();
+        let mut canvas = Canvas::new(10);
+        render(&mut canvas, &parse("*_both_*"), Style::default(), &state);
+
+        assert_eq!(
+            canvas.get_pos(0, 0).unwrap().style,
+            Style::default().modifier(Modifier::Italic)
+        );
+    }
+
+    #[test]
+    fn it_detects_mentions_of_a_user() {
+        let user_id = UserID::from("U1");
+
+        assert!(mentions(&parse("Hi <@U1>"), &user_id));
+        assert!(mentions(&parse("*Hi <@U1>*"), &user_id));
+        assert!(mentions(&parse("> <!here> lunch?"), &user_id));
+        assert!(!mentions(&parse("Hi <@U2>"), &user_id));
+        assert!(!mentions(&parse("`<@U1>`"), &user_id));
+    }
+}
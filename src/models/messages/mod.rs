@@ -1,19 +1,29 @@
+mod channel_event;
+mod channel_start;
 mod error;
 mod loading;
+mod new_messages;
 mod standard;
 mod unsupported;
 
+pub mod encoding;
+pub mod markup;
+
 use std::cmp::{Ord, Ordering, PartialOrd};
 
 use chrono::{DateTime, TimeZone};
 use failure::Error;
+use serde_json::{self, Value};
 use slack::api;
 
-use models::{AppState, Canvas, ChannelID};
+use models::{AppState, Canvas, ChannelID, UserID};
 
+pub use self::channel_event::ChannelEventMessage;
+pub use self::channel_start::ChannelStartMessage;
 pub use self::error::ErrorMessage;
 pub use self::loading::LoadingMessage;
-pub use self::standard::StandardMessage;
+pub use self::new_messages::NewMessagesDivider;
+pub use self::standard::{Reaction, StandardMessage};
 pub use self::unsupported::UnsupportedMessage;
 
 mod prelude {
@@ -29,6 +39,41 @@ pub enum Message {
     Standard(StandardMessage),
     Unsupported(UnsupportedMessage),
     Error(ErrorMessage),
+    ChannelStart(ChannelStartMessage),
+    ChannelEvent(ChannelEventMessage),
+}
+
+/// Something that happened to the history of a channel.
+#[derive(Debug)]
+pub enum MessageEvent {
+    New(Message),
+    Edited {
+        channel_id: ChannelID,
+        message_id: MessageID,
+        body: String,
+    },
+    Deleted {
+        channel_id: ChannelID,
+        message_id: MessageID,
+    },
+    /// The thread of a message got a new reply.
+    Replied {
+        channel_id: ChannelID,
+        message_id: MessageID,
+        reply_count: usize,
+    },
+    ReactionAdded {
+        channel_id: ChannelID,
+        message_id: MessageID,
+        name: String,
+        user_id: UserID,
+    },
+    ReactionRemoved {
+        channel_id: ChannelID,
+        message_id: MessageID,
+        name: String,
+        user_id: UserID,
+    },
 }
 
 pub trait HistoryEntry {
@@ -39,9 +84,14 @@ pub trait HistoryEntry {
     fn into_message(self) -> Message;
 }
 
+/// Details about a message that are missing from `api::Message`.
 #[derive(Debug, Default, Clone)]
 pub struct MessageSideChannel {
     pub channel_id: Option<ChannelID>,
+    pub reply_count: Option<usize>,
+    pub reactions: Vec<Reaction>,
+    /// The message is a thread reply that was also sent to the channel.
+    pub is_broadcast: bool,
 }
 
 fn unsupported(
@@ -58,6 +108,15 @@ fn unsupported(
     }
 }
 
+fn channel_event(
+    id: &Option<String>,
+    text: &Option<String>,
+    side_channel: &MessageSideChannel,
+) -> Result<Option<Message>, Error> {
+    ChannelEventMessage::from_slack_message(id, text, side_channel)
+        .map(|message| message.map(HistoryEntry::into_message))
+}
+
 impl Message {
     pub fn from_slack_message<'a, S>(
         msg: &api::Message,
@@ -86,38 +145,18 @@ impl Message {
             S::FileComment(_) => Ok(None),
             S::FileMention(_) => Ok(None),
             S::FileShare(_) => Ok(None),
-            S::GroupArchive(_) => Ok(None),
-            S::GroupJoin(_) => Ok(None),
-            S::GroupLeave(_) => Ok(None),
-            S::GroupName(_) => Ok(None),
-            S::GroupPurpose(_) => Ok(None),
-            S::GroupTopic(_) => Ok(None),
-            S::GroupUnarchive(_) => Ok(None),
+            S::GroupArchive(ref msg) => channel_event(&msg.ts, &msg.text, &side_channel),
+            S::GroupJoin(ref msg) => channel_event(&msg.ts, &msg.text, &side_channel),
+            S::GroupLeave(ref msg) => channel_event(&msg.ts, &msg.text, &side_channel),
+            S::GroupName(ref msg) => channel_event(&msg.ts, &msg.text, &side_channel),
+            S::GroupPurpose(ref msg) => channel_event(&msg.ts, &msg.text, &side_channel),
+            S::GroupTopic(ref msg) => channel_event(&msg.ts, &msg.text, &side_channel),
+            S::GroupUnarchive(ref msg) => channel_event(&msg.ts, &msg.text, &side_channel),
             S::MeMessage(_) => Ok(None),
-            S::MessageChanged(ref msg) => unsupported(
-                &msg.ts,
-                &msg.channel,
-                &msg.message.as_ref().and_then(|m| m.user.clone()),
-                &msg.message.as_ref().and_then(|c| c.text.clone()),
-                &msg.subtype,
-                &side_channel,
-            ),
-            S::MessageDeleted(ref msg) => unsupported(
-                &msg.ts,
-                &msg.channel,
-                &Some(String::from("Message deleted")),
-                &Some(String::from("Message was deleted")),
-                &msg.subtype,
-                &side_channel,
-            ),
-            S::MessageReplied(ref msg) => unsupported(
-                &msg.ts,
-                &msg.channel,
-                &msg.message.as_ref().and_then(|m| m.user.clone()),
-                &msg.message.as_ref().and_then(|c| c.text.clone()),
-                &msg.subtype,
-                &side_channel,
-            ),
+            // Handled by MessageEvent, as they don't result in new messages.
+            S::MessageChanged(_) => Ok(None),
+            S::MessageDeleted(_) => Ok(None),
+            S::MessageReplied(_) => Ok(None),
             S::PinnedItem(ref msg) => unsupported(
                 &msg.ts,
                 &msg.channel,
@@ -126,14 +165,9 @@ impl Message {
                 &msg.subtype,
                 &side_channel,
             ),
-            S::ReplyBroadcast(ref msg) => unsupported(
-                &msg.ts,
-                &msg.channel,
-                &msg.user,
-                &Some(String::from("Message got a broadcasted reply")),
-                &msg.subtype,
-                &side_channel,
-            ),
+            // Only tells that a reply was also sent to the channel. The reply itself arrives as a
+            // `thread_broadcast` message, which `MessageEvent::from_json` reads.
+            S::ReplyBroadcast(_) => Ok(None),
             S::UnpinnedItem(ref msg) => unsupported(
                 &msg.ts,
                 &msg.channel,
@@ -146,6 +180,187 @@ impl Message {
     }
 }
 
+impl MessageEvent {
+    pub fn from_slack_message<'a, S>(
+        msg: &api::Message,
+        side_channel: S,
+    ) -> Result<Option<Self>, Error>
+    where
+        S: Into<Option<&'a MessageSideChannel>>,
+    {
+        let side_channel = side_channel.into().cloned().unwrap_or_default();
+        match *msg {
+            api::Message::MessageChanged(ref msg) => {
+                MessageEvent::from_message_changed(msg, &side_channel)
+            }
+            api::Message::MessageDeleted(ref msg) => {
+                MessageEvent::from_message_deleted(msg, &side_channel)
+            }
+            api::Message::MessageReplied(ref msg) => {
+                MessageEvent::from_message_replied(msg, &side_channel)
+            }
+            _ => Message::from_slack_message(msg, &side_channel)
+                .map(|message| message.map(MessageEvent::New)),
+        }
+    }
+
+    /// Converts a message from the JSON of a Web API response, keeping the details that
+    /// `api::Message` does not have.
+    pub fn from_json(
+        json: &Value,
+        side_channel: &MessageSideChannel,
+    ) -> Result<Option<Self>, Error> {
+        let mut json = json.clone();
+        let mut side_channel = side_channel.clone();
+        side_channel.reply_count = json
+            .get("reply_count")
+            .and_then(Value::as_u64)
+            .map(|count| count as usize);
+        side_channel.reactions = json
+            .get("reactions")
+            .and_then(Value::as_array)
+            .map(|reactions| reactions.iter().filter_map(Reaction::from_json).collect())
+            .unwrap_or_default();
+
+        // api::Message does not know this subtype, but the message is a standard one otherwise.
+        if json.get("subtype").and_then(Value::as_str) == Some("thread_broadcast") {
+            if let Some(object) = json.as_object_mut() {
+                object.remove("subtype");
+            }
+            side_channel.is_broadcast = true;
+        }
+
+        let message: api::Message = serde_json::from_value(json)?;
+        MessageEvent::from_slack_message(&message, &side_channel)
+    }
+
+    /// Converts a `reaction_added` or `reaction_removed` event from the RTM connection.
+    ///
+    /// `slack::Event` cannot parse these, as it expects the whole message in `item` while Slack
+    /// only sends its channel and ts. Reactions on files are ignored.
+    pub fn from_reaction_json(json: &Value) -> Result<Option<Self>, Error> {
+        let is_added = match json.get("type").and_then(Value::as_str) {
+            Some("reaction_added") => true,
+            Some("reaction_removed") => false,
+            _ => return Err(format_err!("Not a reaction event:\n{:#}", json)),
+        };
+
+        let item = &json["item"];
+        if item.get("type").and_then(Value::as_str) != Some("message") {
+            return Ok(None);
+        }
+
+        let field = |value: &Value, key: &str| -> Result<String, Error> {
+            value
+                .get(key)
+                .and_then(Value::as_str)
+                .map(String::from)
+                .ok_or_else(|| format_err!("Reaction event had no {}:\n{:#}", key, json))
+        };
+        let channel_id = ChannelID::from(field(item, "channel")?);
+        let message_id = MessageID::from(field(item, "ts")?);
+        let name = field(json, "reaction")?;
+        let user_id = UserID::from(field(json, "user")?);
+
+        Ok(Some(if is_added {
+            MessageEvent::ReactionAdded {
+                channel_id,
+                message_id,
+                name,
+                user_id,
+            }
+        } else {
+            MessageEvent::ReactionRemoved {
+                channel_id,
+                message_id,
+                name,
+                user_id,
+            }
+        }))
+    }
+
+    fn from_message_changed(
+        msg: &api::MessageMessageChanged,
+        side_channel: &MessageSideChannel,
+    ) -> Result<Option<Self>, Error> {
+        let channel_id = msg
+            .channel
+            .clone()
+            .map(ChannelID::from)
+            .or_else(|| side_channel.channel_id.clone())
+            .ok_or_else(|| format_err!("Message change had no channel:\n{:#?}", msg))?;
+
+        let inner = match msg.message {
+            Some(ref inner) => inner,
+            None => return Err(format_err!("Message change had no message:\n{:#?}", msg)),
+        };
+
+        let message_id = inner
+            .ts
+            .clone()
+            .map(MessageID::from)
+            .ok_or_else(|| format_err!("Changed message had no ts:\n{:#?}", msg))?;
+
+        Ok(Some(MessageEvent::Edited {
+            channel_id,
+            message_id,
+            body: inner.text.clone().unwrap_or_else(String::new),
+        }))
+    }
+
+    fn from_message_deleted(
+        msg: &api::MessageMessageDeleted,
+        side_channel: &MessageSideChannel,
+    ) -> Result<Option<Self>, Error> {
+        let channel_id = msg
+            .channel
+            .clone()
+            .map(ChannelID::from)
+            .or_else(|| side_channel.channel_id.clone())
+            .ok_or_else(|| format_err!("Message deletion had no channel:\n{:#?}", msg))?;
+
+        let message_id = msg
+            .deleted_ts
+            .clone()
+            .map(MessageID::from)
+            .ok_or_else(|| format_err!("Message deletion had no deleted_ts:\n{:#?}", msg))?;
+
+        Ok(Some(MessageEvent::Deleted {
+            channel_id,
+            message_id,
+        }))
+    }
+
+    fn from_message_replied(
+        msg: &api::MessageMessageReplied,
+        side_channel: &MessageSideChannel,
+    ) -> Result<Option<Self>, Error> {
+        let channel_id = msg
+            .channel
+            .clone()
+            .map(ChannelID::from)
+            .or_else(|| side_channel.channel_id.clone())
+            .ok_or_else(|| format_err!("Message reply had no channel:\n{:#?}", msg))?;
+
+        let inner = match msg.message {
+            Some(ref inner) => inner,
+            None => return Err(format_err!("Message reply had no message:\n{:#?}", msg)),
+        };
+
+        let message_id = inner
+            .ts
+            .clone()
+            .map(MessageID::from)
+            .ok_or_else(|| format_err!("Replied message had no ts:\n{:#?}", msg))?;
+
+        Ok(Some(MessageEvent::Replied {
+            channel_id,
+            message_id,
+            reply_count: inner.reply_count.unwrap_or(0) as usize,
+        }))
+    }
+}
+
 impl HistoryEntry for Message {
     fn id(&self) -> &MessageID {
         use self::Message::*;
@@ -153,6 +368,8 @@ impl HistoryEntry for Message {
             Standard(ref msg) => msg.id(),
             Unsupported(ref msg) => msg.id(),
             Error(ref msg) => msg.id(),
+            ChannelStart(ref msg) => msg.id(),
+            ChannelEvent(ref msg) => msg.id(),
         }
     }
 
@@ -162,6 +379,8 @@ impl HistoryEntry for Message {
             Standard(ref msg) => msg.channel_id(),
             Unsupported(ref msg) => msg.channel_id(),
             Error(ref msg) => msg.channel_id(),
+            ChannelStart(ref msg) => msg.channel_id(),
+            ChannelEvent(ref msg) => msg.channel_id(),
         }
     }
 
@@ -171,6 +390,8 @@ impl HistoryEntry for Message {
             Standard(ref msg) => msg.render_as_canvas(state, width),
             Unsupported(ref msg) => msg.render_as_canvas(state, width),
             Error(ref msg) => msg.render_as_canvas(state, width),
+            ChannelStart(ref msg) => msg.render_as_canvas(state, width),
+            ChannelEvent(ref msg) => msg.render_as_canvas(state, width),
         }
     }
 
@@ -195,10 +416,6 @@ impl MessageID {
     pub fn as_str(&self) -> &str {
         &self.0
     }
-
-    pub fn as_string(&self) -> String {
-        self.0.clone()
-    }
 }
 
 impl From<String> for MessageID {
@@ -252,4 +469,108 @@ mod tests {
             assert_eq!(&id.0, expected_id);
         }
     }
+
+    mod message_event {
+        use super::*;
+
+        fn standard_message_from_json(json: &str) -> StandardMessage {
+            let json: Value = serde_json::from_str(json).unwrap();
+            let side_channel = MessageSideChannel {
+                channel_id: Some("C1".into()),
+                ..Default::default()
+            };
+            match MessageEvent::from_json(&json, &side_channel) {
+                Ok(Some(MessageEvent::New(Message::Standard(message)))) => message,
+                other => panic!("Unexpected event: {:?}", other),
+            }
+        }
+
+        #[test]
+        fn it_reads_reply_counts_from_json() {
+            let message = standard_message_from_json(
+                r#"{"type": "message", "user": "U1", "text": "Lunch?", "ts": "1111.0",
+                    "thread_ts": "1111.0", "reply_count": 3}"#,
+            );
+
+            assert_eq!(message.reply_count, 3);
+            assert!(!message.is_broadcast);
+        }
+
+        #[test]
+        fn it_keeps_broadcast_replies_from_json() {
+            let message = standard_message_from_json(
+                r#"{"type": "message", "subtype": "thread_broadcast", "user": "U1",
+                    "text": "Yes!", "ts": "1112.0", "thread_ts": "1111.0"}"#,
+            );
+
+            assert!(message.is_thread_reply());
+            assert!(message.is_broadcast);
+            assert!(message.is_shown_in_channel());
+        }
+
+        #[test]
+        fn it_reads_reactions_from_json() {
+            let message = standard_message_from_json(
+                r#"{"type": "message", "user": "U1", "text": "Ship it", "ts": "1111.0",
+                    "reactions": [
+                        {"name": "+1", "users": ["U1", "U2"], "count": 2},
+                        {"name": "tada", "users": ["U3"], "count": 1}
+                    ]}"#,
+            );
+
+            assert_eq!(
+                message.reactions,
+                vec![
+                    Reaction {
+                        name: "+1".into(),
+                        user_ids: vec!["U1".into(), "U2".into()],
+                    },
+                    Reaction {
+                        name: "tada".into(),
+                        user_ids: vec!["U3".into()],
+                    },
+                ]
+            );
+            assert!(message.has_reaction_from("+1", &"U2".into()));
+        }
+
+        #[test]
+        fn it_reads_reaction_events_from_json() {
+            let json: Value = serde_json::from_str(
+                r#"{"type": "reaction_added", "user": "U024BE7LH", "reaction": "thumbsup",
+                    "item_user": "U0G9QF9C6",
+                    "item": {"type": "message", "channel": "C0G9QF9GZ",
+                             "ts": "1360782400.498405"},
+                    "event_ts": "1360782804.083113"}"#,
+            )
+            .unwrap();
+
+            match MessageEvent::from_reaction_json(&json) {
+                Ok(Some(MessageEvent::ReactionAdded {
+                    channel_id,
+                    message_id,
+                    name,
+                    user_id,
+                })) => {
+                    assert_eq!(channel_id, ChannelID::from("C0G9QF9GZ"));
+                    assert_eq!(message_id, MessageID::from("1360782400.498405"));
+                    assert_eq!(name, "thumbsup");
+                    assert_eq!(user_id, UserID::from("U024BE7LH"));
+                }
+                other => panic!("Unexpected event: {:?}", other),
+            }
+        }
+
+        #[test]
+        fn it_ignores_reactions_on_files() {
+            let json: Value = serde_json::from_str(
+                r#"{"type": "reaction_removed", "user": "U024BE7LH", "reaction": "thumbsup",
+                    "item_user": "U0G9QF9C6", "item": {"type": "file", "file": "F0HS27V1Z"},
+                    "event_ts": "1360782804.083113"}"#,
+            )
+            .unwrap();
+
+            assert!(MessageEvent::from_reaction_json(&json).unwrap().is_none());
+        }
+    }
 }
@@ -0,0 +1,43 @@
+use models::{AppState, Canvas};
+
+/// Separates the messages the user has already read from the new ones.
+#[derive(Clone, Debug)]
+pub struct NewMessagesDivider {}
+
+impl NewMessagesDivider {
+    pub fn new() -> Self {
+        NewMessagesDivider {}
+    }
+
+    pub fn render_as_canvas(&self, _state: &AppState, width: u16) -> Canvas {
+        use tui::style::*;
+
+        let mut canvas = Canvas::new(width);
+        canvas.add_string_truncated(
+            &format!("{:─^1$}", " New messages ", width as usize),
+            Style::default().fg(Color::Red),
+        );
+
+        canvas
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_renders_as_canvas() {
+        let state = AppState::fixture();
+        let divider = NewMessagesDivider::new();
+
+        let canvas = divider.render_as_canvas(&state, 30);
+        assert_eq!(
+            &canvas.render_to_string(Some("|")),
+            "──────── New messages ────────|"
+        );
+
+        let small_canvas = divider.render_as_canvas(&state, 10);
+        assert_eq!(&small_canvas.render_to_string(Some("|")), " New messa|");
+    }
+}
@@ -2,8 +2,10 @@ use std::cmp::{Ord, Ordering, PartialOrd};
 use std::hash::{Hash, Hasher};
 
 use failure::Error;
+use serde_json::Value;
 use slack::api;
 
+use super::markup;
 use super::prelude::*;
 use models::UserID;
 
@@ -14,6 +16,34 @@ pub struct StandardMessage {
     pub channel_id: ChannelID,
     pub user_id: UserID,
     pub body: String,
+    pub is_edited: bool,
+    /// The message is a thread reply that was also sent to the channel.
+    pub is_broadcast: bool,
+    /// Number of replies in the thread started by this message.
+    pub reply_count: usize,
+    pub reactions: Vec<Reaction>,
+}
+
+/// An emoji reaction on a message, and the users that reacted with it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Reaction {
+    pub name: String,
+    pub user_ids: Vec<UserID>,
+}
+
+impl Reaction {
+    /// Reads a reaction from the `reactions` of a message in a Web API response.
+    pub fn from_json(json: &Value) -> Option<Reaction> {
+        let name = json.get("name")?.as_str()?.to_owned();
+        let user_ids = json
+            .get("users")?
+            .as_array()?
+            .iter()
+            .filter_map(Value::as_str)
+            .map(UserID::from)
+            .collect();
+        Some(Reaction { name, user_ids })
+    }
 }
 
 impl StandardMessage {
@@ -38,7 +68,7 @@ impl StandardMessage {
 
         let message_id = MessageID::from(ts);
         let thread_id = msg
-            .ts
+            .thread_ts
             .clone()
             .map(MessageID::from)
             .unwrap_or_else(|| message_id.clone());
@@ -49,8 +79,82 @@ impl StandardMessage {
             channel_id,
             body: msg.text.clone().unwrap_or_else(|| String::new()),
             user_id: msg.user.clone().map(UserID::from).unwrap(),
+            is_edited: msg.edited.is_some(),
+            is_broadcast: side_channel.is_broadcast,
+            reply_count: side_channel.reply_count.unwrap_or(0),
+            reactions: side_channel.reactions.clone(),
         })
     }
+
+    /// Returns true if the message was posted in the thread of another message.
+    pub fn is_thread_reply(&self) -> bool {
+        self.thread_id != self.message_id
+    }
+
+    /// Returns true if the message belongs in the history of the channel, and not only in the
+    /// thread pane.
+    pub fn is_shown_in_channel(&self) -> bool {
+        !self.is_thread_reply() || self.is_broadcast
+    }
+
+    pub fn has_reaction_from(&self, name: &str, user_id: &UserID) -> bool {
+        self.reactions
+            .iter()
+            .any(|reaction| reaction.name == name && reaction.user_ids.contains(user_id))
+    }
+
+    pub fn add_reaction(&mut self, name: &str, user_id: UserID) {
+        if self.has_reaction_from(name, &user_id) {
+            return;
+        }
+
+        match self.reactions.iter().position(|reaction| reaction.name == name) {
+            Some(index) => self.reactions[index].user_ids.push(user_id),
+            None => self.reactions.push(Reaction {
+                name: name.to_owned(),
+                user_ids: vec![user_id],
+            }),
+        }
+    }
+
+    pub fn remove_reaction(&mut self, name: &str, user_id: &UserID) {
+        for reaction in &mut self.reactions {
+            if reaction.name == name {
+                reaction.user_ids.retain(|id| id != user_id);
+            }
+        }
+        self.reactions.retain(|reaction| !reaction.user_ids.is_empty());
+    }
+
+    /// Returns true if the message mentions the user, directly or through `@here` and similar.
+    pub fn mentions(&self, user_id: &UserID) -> bool {
+        markup::mentions(&markup::parse(&self.body), user_id)
+    }
+}
+
+/// Renders reactions on a single line, like `👍 3  :party_parrot: 1`. Reactions from the user are
+/// highlighted.
+fn render_reactions(canvas: &mut Canvas, reactions: &[Reaction], user_id: &UserID) {
+    use models::emoji;
+    use tui::style::*;
+
+    for (i, reaction) in reactions.iter().enumerate() {
+        let style = if reaction.user_ids.contains(user_id) {
+            Style::default().fg(Color::Cyan).modifier(Modifier::Bold)
+        } else {
+            Style::default()
+        };
+
+        if i > 0 {
+            canvas.add_string_wrapped("  ", Style::default());
+        }
+        match emoji::character_for(&reaction.name) {
+            Some(character) => canvas.add_wide_character(character, style),
+            None => canvas.add_string_wrapped(&format!(":{}:", reaction.name), style),
+        }
+        canvas.add_string_wrapped(&format!(" {}", reaction.user_ids.len()), style);
+    }
+    canvas.add_string_wrapped("\n", Style::default());
 }
 
 impl Hash for StandardMessage {
@@ -105,7 +209,27 @@ impl HistoryEntry for StandardMessage {
             }
         }
         canvas.add_string_truncated("\n", Style::default());
-        canvas.add_string_wrapped(&format!("{}\n", self.body), Style::default());
+        markup::render(
+            &mut canvas,
+            &markup::parse(&self.body),
+            Style::default(),
+            state,
+        );
+        if self.is_edited {
+            canvas.add_string_wrapped(" (edited)", Style::default().modifier(Modifier::Faint));
+        }
+        canvas.add_string_wrapped("\n", Style::default());
+        if !self.reactions.is_empty() {
+            render_reactions(&mut canvas, &self.reactions, &state.current_user_id);
+        }
+        if self.reply_count > 0 {
+            let replies = match self.reply_count {
+                1 => String::from("1 reply"),
+                count => format!("{} replies", count),
+            };
+            canvas.add_string_truncated(&replies, Style::default().fg(Color::LightCyan));
+            canvas.add_string_truncated("\n", Style::default());
+        }
 
         canvas
     }
@@ -147,6 +271,7 @@ mod tests {
 
         let side_channel = MessageSideChannel {
             channel_id: Some(ChannelID::from("C123")),
+            ..Default::default()
         };
 
         let message_not_using_side_channel =
@@ -178,6 +303,10 @@ mod tests {
             message_id: "1110000.0000".into(),
             thread_id: "1110000.0000".into(),
             channel_id: "C1".into(),
+            is_edited: false,
+            is_broadcast: false,
+            reply_count: 0,
+            reactions: Vec::new(),
         };
 
         let big_canvas = message.render_as_canvas(&state, 50);
@@ -197,6 +326,185 @@ have to drink my own|
         );
     }
 
+    #[test]
+    fn it_renders_edited_messages() {
+        let state = AppState::fixture();
+        let message = StandardMessage {
+            user_id: "U1".into(),
+            body: "Fixed my typo".into(),
+            message_id: "1110000.0000".into(),
+            thread_id: "1110000.0000".into(),
+            channel_id: "C1".into(),
+            is_edited: true,
+            is_broadcast: false,
+            reply_count: 0,
+            reactions: Vec::new(),
+        };
+
+        let canvas = message.render_as_canvas(&state, 30);
+        assert_eq!(
+            &canvas.render_to_string(Some("|")),
+            "U1                            |
+Fixed my typo (edited)        |",
+        );
+    }
+
+    #[test]
+    fn it_renders_reply_count() {
+        let state = AppState::fixture();
+        let mut message = StandardMessage {
+            user_id: "U1".into(),
+            body: "Who wants lunch?".into(),
+            message_id: "1110000.0000".into(),
+            thread_id: "1110000.0000".into(),
+            channel_id: "C1".into(),
+            is_edited: false,
+            is_broadcast: false,
+            reply_count: 1,
+            reactions: Vec::new(),
+        };
+
+        let canvas = message.render_as_canvas(&state, 20);
+        assert_eq!(
+            &canvas.render_to_string(Some("|")),
+            "U1                  |
+Who wants lunch?    |
+1 reply             |",
+        );
+
+        message.reply_count = 12;
+        let canvas = message.render_as_canvas(&state, 20);
+        assert!(canvas.render_to_string(None).contains("12 replies"));
+    }
+
+    #[test]
+    fn it_renders_reactions() {
+        use tui::style::*;
+
+        let state = AppState::fixture();
+        let mut message = StandardMessage {
+            user_id: "U1".into(),
+            body: "Shipped!".into(),
+            message_id: "1110000.0000".into(),
+            thread_id: "1110000.0000".into(),
+            channel_id: "C1".into(),
+            is_edited: false,
+            is_broadcast: false,
+            reply_count: 0,
+            reactions: Vec::new(),
+        };
+        message.add_reaction("+1", "U1".into());
+        message.add_reaction("party_parrot", "U2".into());
+        message.add_reaction("+1", "U0".into());
+
+        let canvas = message.render_as_canvas(&state, 25);
+        assert_eq!(
+            &canvas.render_to_string(Some("|")),
+            "U1                       |
+Shipped!                 |
+👍 2  :party_parrot: 1   |",
+        );
+
+        // The fixture user is U0, who reacted with +1.
+        assert_eq!(
+            canvas.get_pos(3, 2).unwrap().style,
+            Style::default().fg(Color::Cyan).modifier(Modifier::Bold)
+        );
+        assert_eq!(canvas.get_pos(6, 2).unwrap().style, Style::default());
+    }
+
+    #[test]
+    fn it_adds_and_removes_reactions() {
+        let mut message = StandardMessage {
+            user_id: "U1".into(),
+            body: "Hello".into(),
+            message_id: "1110000.0000".into(),
+            thread_id: "1110000.0000".into(),
+            channel_id: "C1".into(),
+            is_edited: false,
+            is_broadcast: false,
+            reply_count: 0,
+            reactions: Vec::new(),
+        };
+
+        message.add_reaction("wave", "U1".into());
+        message.add_reaction("wave", "U1".into());
+        message.add_reaction("wave", "U2".into());
+        assert_eq!(message.reactions.len(), 1);
+        assert_eq!(message.reactions[0].user_ids.len(), 2);
+        assert!(message.has_reaction_from("wave", &"U2".into()));
+
+        message.remove_reaction("wave", &"U1".into());
+        assert!(!message.has_reaction_from("wave", &"U1".into()));
+        message.remove_reaction("wave", &"U2".into());
+        assert!(message.reactions.is_empty());
+    }
+
+    #[test]
+    fn it_takes_reactions_from_side_channel() {
+        let reaction = Reaction {
+            name: "tada".into(),
+            user_ids: vec!["U1".into()],
+        };
+        let side_channel = MessageSideChannel {
+            reactions: vec![reaction.clone()],
+            ..Default::default()
+        };
+
+        let message = StandardMessage::from_slack(&fake_slack_message(), &side_channel).unwrap();
+        assert_eq!(message.reactions, vec![reaction]);
+        assert!(message.has_reaction_from("tada", &"U1".into()));
+    }
+
+    #[test]
+    fn it_takes_thread_from_thread_ts() {
+        let mut slack_message = fake_slack_message();
+        let side_channel = MessageSideChannel {
+            reply_count: Some(1),
+            ..Default::default()
+        };
+
+        let parent = StandardMessage::from_slack(&slack_message, &side_channel).unwrap();
+        assert_eq!(parent.thread_id, MessageID::from("1111"));
+        assert_eq!(parent.reply_count, 1);
+        assert!(!parent.is_thread_reply());
+
+        slack_message.ts = Some(String::from("1112"));
+        slack_message.thread_ts = Some(String::from("1111"));
+        let reply = StandardMessage::from_slack(&slack_message, &side_channel).unwrap();
+        assert_eq!(reply.thread_id, MessageID::from("1111"));
+        assert!(reply.is_thread_reply());
+    }
+
+    #[test]
+    fn it_renders_formatted_body() {
+        use tui::style::*;
+
+        let state = AppState::fixture();
+        let message = StandardMessage {
+            user_id: "U1".into(),
+            body: "This is *important*".into(),
+            message_id: "1110000.0000".into(),
+            thread_id: "1110000.0000".into(),
+            channel_id: "C1".into(),
+            is_edited: false,
+            is_broadcast: false,
+            reply_count: 0,
+            reactions: Vec::new(),
+        };
+
+        let canvas = message.render_as_canvas(&state, 30);
+        assert_eq!(
+            &canvas.render_to_string(Some("|")),
+            "U1                            |
+This is important             |",
+        );
+        assert_eq!(
+            canvas.get_pos(8, 1).unwrap().style,
+            Style::default().modifier(Modifier::Bold)
+        );
+    }
+
     #[test]
     fn it_renders_messages_with_many_characters() {
         let state = AppState::fixture();
@@ -206,6 +514,10 @@ have to drink my own|
             message_id: "1110000.0000".into(),
             thread_id: "1110000.0000".into(),
             channel_id: "C1".into(),
+            is_edited: false,
+            is_broadcast: false,
+            reply_count: 0,
+            reactions: Vec::new(),
         };
 
         let big_canvas = message.render_as_canvas(&state, 50);
@@ -5,6 +5,7 @@ mod messages;
 mod user;
 
 pub mod canvas;
+pub mod emoji;
 
 pub use self::app_state::*;
 pub use self::canvas::Canvas;
@@ -94,6 +94,13 @@ impl UserList {
     pub fn get(&self, id: &UserID) -> Option<&User> {
         self.users.get(id)
     }
+
+    /// Finds a user by display name, ignoring case.
+    pub fn find_by_name(&self, name: &str) -> Option<&User> {
+        self.users
+            .values()
+            .find(|user| user.display_name.eq_ignore_ascii_case(name))
+    }
 }
 
 impl UserID {
@@ -1,29 +1,39 @@
 use std::cmp::{Eq, Ord, Ordering, PartialEq};
+use std::collections::HashMap;
 
 use tui::buffer::Buffer;
 use tui::layout::Rect;
 use tui::style::*;
 use tui::widgets::Widget;
 
-use models::{self, ChannelID};
+use models::{self, ChannelID, ChannelKind, UnreadCount};
 
 pub struct ChannelList<'a> {
     channels: &'a models::ChannelList,
     selected_id: &'a ChannelID,
+    unreads: &'a HashMap<ChannelID, UnreadCount>,
 }
 
 struct ChannelEntry<'a> {
     id: &'a ChannelID,
     name: &'a str,
+    sigil: char,
+    is_private: bool,
     has_unreads: bool,
+    mention_count: usize,
     is_selected: bool,
 }
 
 impl<'a> ChannelList<'a> {
-    pub fn new(channels: &'a models::ChannelList, selected_id: &'a ChannelID) -> Self {
+    pub fn new(
+        channels: &'a models::ChannelList,
+        selected_id: &'a ChannelID,
+        unreads: &'a HashMap<ChannelID, UnreadCount>,
+    ) -> Self {
         ChannelList {
             channels,
             selected_id,
+            unreads,
         }
     }
 }
@@ -36,16 +46,27 @@ impl<'a> Widget for ChannelList<'a> {
 
         let mut starred = vec![];
         let mut others = vec![];
+        let mut direct_messages = vec![];
 
         for (_, channel) in self.channels.iter() {
+            let unread = self
+                .unreads
+                .get(channel.id())
+                .cloned()
+                .unwrap_or_default();
             let entry = ChannelEntry {
                 id: channel.id(),
                 name: channel.name(),
-                has_unreads: false,
+                sigil: channel.sigil(),
+                is_private: channel.kind() == ChannelKind::Private,
+                has_unreads: unread.messages > 0,
+                mention_count: unread.mentions,
                 is_selected: self.selected_id == channel.id(),
             };
             if channel.is_starred() {
                 starred.push(entry);
+            } else if channel.kind().is_direct_message() {
+                direct_messages.push(entry);
             } else if channel.is_member() || entry.is_selected {
                 others.push(entry);
             }
@@ -53,10 +74,12 @@ impl<'a> Widget for ChannelList<'a> {
 
         starred.sort();
         others.sort();
+        direct_messages.sort();
 
         let mut y = area.top();
         y = draw_entries("Starred", &starred, area, buf, y);
-        draw_entries("Channels", &others, area, buf, y);
+        y = draw_entries("Channels", &others, area, buf, y);
+        draw_entries("Direct messages", &direct_messages, area, buf, y);
     }
 }
 
@@ -78,38 +101,72 @@ fn draw_entries(
         y += 1;
     }
 
-    // Subtracted one character for icon
-    let name_width = area.width as usize - 1;
-
     for entry in entries {
         if y >= area.bottom() {
             return y;
         }
 
+        // The lock is a wide character, so it covers the cell after it too.
+        let icon_width = if entry.is_private { 2 } else { 1 };
+        let name_width = area.width as usize - icon_width;
+        let name_x = area.x + icon_width as u16;
+
         let mut style = Style::default();
 
         if entry.has_unreads {
-            style.modifier(Modifier::Bold);
+            style = style.modifier(Modifier::Bold);
         }
 
         if entry.is_selected {
             style = style.bg(Color::White).fg(Color::Black);
         }
 
-        buf.set_stringn(area.x, y, "#", 1, &style);
+        // Mentions are shown as a badge at the end of the line.
+        let badge = if entry.mention_count > 0 {
+            format!(" {} ", entry.mention_count)
+        } else {
+            String::new()
+        };
+        let badge_width = badge.len().min(name_width);
+        let text_width = name_width - badge_width;
+
+        if entry.is_private {
+            set_wide_symbol(buf, area.x, y, "🔒", style);
+        } else {
+            buf.set_stringn(area.x, y, &entry.sigil.to_string(), 1, &style);
+        }
         buf.set_stringn(
-            area.x + 1,
+            name_x,
             y,
-            &format!("{:1$}", entry.name, name_width),
-            name_width,
+            &format!("{:1$}", entry.name, text_width),
+            text_width,
             &style,
         );
+        if badge_width > 0 {
+            buf.set_stringn(
+                name_x + text_width as u16,
+                y,
+                &badge,
+                badge_width,
+                &Style::default()
+                    .bg(Color::Red)
+                    .fg(Color::White)
+                    .modifier(Modifier::Bold),
+            );
+        }
         y += 1;
     }
 
     y
 }
 
+/// Sets a symbol that is two cells wide in the terminal, like most emoji. The second cell gets an
+/// empty symbol so the terminal does not draw anything over the right half of the symbol.
+fn set_wide_symbol(buf: &mut Buffer, x: u16, y: u16, symbol: &str, style: Style) {
+    buf.get_mut(x, y).set_symbol(symbol).set_style(style);
+    buf.get_mut(x + 1, y).set_symbol("").set_style(style);
+}
+
 impl<'a> PartialEq for ChannelEntry<'a> {
     fn eq(&self, other: &ChannelEntry) -> bool {
         self.id.eq(other.id)
@@ -118,14 +175,56 @@ impl<'a> PartialEq for ChannelEntry<'a> {
 
 impl<'a> Eq for ChannelEntry<'a> {}
 
+// Sorted by name, so channels don't jump around in the sidebar as messages arrive. The ID breaks
+// ties between entries with the same name, like DMs with users sharing a display name.
 impl<'a> PartialOrd for ChannelEntry<'a> {
     fn partial_cmp(&self, rhs: &ChannelEntry) -> Option<Ordering> {
-        (self.has_unreads, self.name).partial_cmp(&(rhs.has_unreads, &rhs.name))
+        Some(self.cmp(rhs))
     }
 }
 
 impl<'a> Ord for ChannelEntry<'a> {
     fn cmp(&self, rhs: &ChannelEntry) -> Ordering {
-        (self.has_unreads, self.name).cmp(&(rhs.has_unreads, &rhs.name))
+        (self.name, self.id).cmp(&(rhs.name, rhs.id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use models::Channel;
+
+    #[test]
+    fn it_draws_the_lock_of_private_channels_across_two_cells() {
+        let mut channels = models::ChannelList::new();
+        channels.add_channel(Channel::private_fixture("G1", "secret"));
+        let selected_id = ChannelID::from("G1");
+        let unreads = HashMap::new();
+
+        let area = Rect::new(0, 0, 10, 3);
+        let mut buf = Buffer::empty(area);
+        ChannelList::new(&channels, &selected_id, &unreads).draw(&area, &mut buf);
+
+        let row: Vec<&str> = (0..10).map(|x| buf.get(x, 2).symbol.as_str()).collect();
+        assert_eq!(row, vec!["🔒", "", "s", "e", "c", "r", "e", "t", " ", " "]);
+    }
+
+    #[test]
+    fn it_orders_entries_with_the_same_name_by_id() {
+        let first_id = ChannelID::from("D1");
+        let second_id = ChannelID::from("D2");
+        let entry = |id| ChannelEntry {
+            id,
+            name: "alex",
+            sigil: '@',
+            is_private: false,
+            has_unreads: false,
+            mention_count: 0,
+            is_selected: false,
+        };
+
+        assert_eq!(entry(&first_id).cmp(&entry(&second_id)), Ordering::Less);
+        assert_eq!(entry(&second_id).cmp(&entry(&first_id)), Ordering::Greater);
+        assert_eq!(entry(&first_id).cmp(&entry(&first_id)), Ordering::Equal);
     }
 }
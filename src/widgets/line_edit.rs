@@ -1,3 +1,5 @@
+use std::iter;
+
 use tui::buffer::Buffer;
 use tui::layout::Rect;
 use tui::style::*;
@@ -25,6 +27,7 @@ impl<'a> LineEdit<'a> {
         self
     }
 
+    /// Sets the position of the cursor, counted in characters.
     pub fn cursor_pos(&mut self, pos: usize) -> &mut Self {
         self.cursor_pos = pos;
         self
@@ -38,27 +41,27 @@ impl<'a> LineEdit<'a> {
 
 impl<'a> Widget for LineEdit<'a> {
     fn draw(&mut self, area: &Rect, buf: &mut Buffer) {
+        // The cursor position is counted in characters, so work on those instead of bytes.
+        let chars: Vec<char> = self.text.chars().collect();
+
         // Leave one extra cell for cursor
-        let offset = self
-            .text
+        let offset = chars
             .len()
             .saturating_sub(area.width as usize)
             .saturating_sub(1)
             .min(self.cursor_pos); // Keep cursor inside viewport
 
-        let text_width = (self.text.len() - offset).min(area.width as usize);
-
         // Pick the right side of the text, offset by the offset specified.
         // Left pad with spaces so entire input box is rendered.
-        let drawn_text = format!(
-            "{:<1$}",
-            &self.text[offset..offset + text_width],
-            area.width as usize
-        );
+        let drawn_text = chars[offset..]
+            .iter()
+            .cloned()
+            .chain(iter::repeat(' '))
+            .take(area.width as usize);
 
         let cursor_style = Style::default().fg(self.style.bg).bg(self.style.fg);
 
-        for (i, chr) in drawn_text.chars().enumerate() {
+        for (i, chr) in drawn_text.enumerate() {
             let style = if self.cursor_pos >= offset && i == self.cursor_pos - offset {
                 cursor_style
             } else {
@@ -74,3 +77,24 @@ impl<'a> Widget for LineEdit<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_scrolls_text_by_characters() {
+        let area = Rect::new(0, 0, 5, 1);
+        let mut buf = Buffer::empty(area);
+        LineEdit::default()
+            .text("åäöåäöåä")
+            .cursor_pos(3)
+            .style(Style::default().fg(Color::White).bg(Color::Black))
+            .draw(&area, &mut buf);
+
+        let drawn: String = (0..5).map(|x| buf.get(x, 0).symbol.clone()).collect();
+        assert_eq!(drawn, "öåäöå");
+        assert_eq!(buf.get(1, 0).style.bg, Color::White);
+        assert_eq!(buf.get(2, 0).style.bg, Color::Black);
+    }
+}
@@ -2,8 +2,10 @@ mod channel_list;
 mod chat_history;
 mod line_edit;
 mod scrollbar;
+mod text_edit;
 
 pub use self::channel_list::ChannelList;
 pub use self::chat_history::ChatHistory;
 pub use self::line_edit::LineEdit;
 pub use self::scrollbar::Scrollbar;
+pub use self::text_edit::TextEdit;
@@ -0,0 +1,100 @@
+use tui::buffer::Buffer;
+use tui::layout::Rect;
+use tui::style::*;
+use tui::widgets::Widget;
+
+/// Multi-line version of `LineEdit`.
+///
+/// Scrolls both vertically and horizontally to keep the cursor inside the area.
+pub struct TextEdit<'a> {
+    text: &'a str,
+    placeholder: &'a str,
+    cursor: Option<(usize, usize)>,
+    style: Style,
+}
+
+impl<'a> Default for TextEdit<'a> {
+    fn default() -> Self {
+        TextEdit {
+            text: "",
+            placeholder: "",
+            cursor: None,
+            style: Style::default(),
+        }
+    }
+}
+
+impl<'a> TextEdit<'a> {
+    pub fn text(&mut self, text: &'a str) -> &mut Self {
+        self.text = text;
+        self
+    }
+
+    /// Text to show in a faint color when the text is empty.
+    pub fn placeholder(&mut self, placeholder: &'a str) -> &mut Self {
+        self.placeholder = placeholder;
+        self
+    }
+
+    /// Line and column (in characters) of the cursor. The cursor is not drawn when `None`.
+    pub fn cursor(&mut self, cursor: Option<(usize, usize)>) -> &mut Self {
+        self.cursor = cursor;
+        self
+    }
+
+    pub fn style(&mut self, style: Style) -> &mut Self {
+        self.style = style;
+        self
+    }
+}
+
+impl<'a> Widget for TextEdit<'a> {
+    fn draw(&mut self, area: &Rect, buf: &mut Buffer) {
+        if area.width == 0 || area.height == 0 {
+            return;
+        }
+
+        let width = area.width as usize;
+        let height = area.height as usize;
+        let (cursor_line, cursor_column) = self.cursor.unwrap_or((0, 0));
+
+        // Keep cursor inside viewport. Leave one extra cell for cursor on the right side.
+        let line_offset = (cursor_line + 1).saturating_sub(height);
+        let column_offset = (cursor_column + 1).saturating_sub(width);
+
+        let cursor_style = Style::default().fg(self.style.bg).bg(self.style.fg);
+        let placeholder_style = self.style.clone().fg(Color::DarkGray);
+
+        let (text, text_style) = if self.text.is_empty() && self.cursor.is_none() {
+            (self.placeholder, placeholder_style)
+        } else {
+            (self.text, self.style)
+        };
+
+        let mut lines = text.split('\n').skip(line_offset);
+        for y in 0..height {
+            let line = lines.next().unwrap_or("");
+            let line_index = line_offset + y;
+            let mut chars = line.chars().skip(column_offset);
+
+            for x in 0..width {
+                let chr = chars.next().unwrap_or(' ');
+                let style = if self.cursor.is_some()
+                    && line_index == cursor_line
+                    && column_offset + x == cursor_column
+                {
+                    cursor_style
+                } else {
+                    text_style
+                };
+
+                let buf_index = buf.index_of(area.x + x as u16, area.y + y as u16);
+                buf.content
+                    .get_mut(buf_index)
+                    .unwrap()
+                    .set_char(chr)
+                    .set_style(style);
+            }
+        }
+    }
+}